@@ -0,0 +1,466 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+/// When entries are dated, 1980-01-01 in MS-DOS format. A fixed date keeps
+/// archives of the same docs identical.
+const DOS_DATE: u16 = (1 << 5) | 1;
+const VERSION: u16 = 20;
+/// Names are UTF-8.
+const FLAGS: u16 = 1 << 11;
+
+/// How an entry's data is kept in the archive.
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    method: u16,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+impl Entry {
+    /// The fields the local and central headers share, from the version
+    /// needed up to the extra field length.
+    fn fields(&self, header: &mut Vec<u8>) -> Result<()> {
+        header.extend(VERSION.to_le_bytes());
+        header.extend(FLAGS.to_le_bytes());
+        header.extend(self.method.to_le_bytes());
+        // At midnight
+        header.extend(0u16.to_le_bytes());
+        header.extend(DOS_DATE.to_le_bytes());
+        header.extend(self.crc.to_le_bytes());
+        header.extend(self.compressed_size.to_le_bytes());
+        header.extend(self.size.to_le_bytes());
+        header.extend(fits::<u16>(self.name.len(), "file name")?.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        Ok(())
+    }
+}
+
+/// Zip offsets and counts are 32 and 16 bit, larger archives would need the
+/// zip64 extension.
+fn fits<T: TryFrom<usize>>(value: usize, what: &str) -> Result<T> {
+    T::try_from(value).map_err(|_| anyhow!("The {} is too large for a zip archive", what))
+}
+
+fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    for (n, crc) in (0u32..).zip(table.iter_mut()) {
+        *crc = (0..8).fold(n, |c, _| {
+            if c & 1 == 1 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            }
+        });
+    }
+    table
+}
+
+fn crc32(table: &[u32; 256], data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        table[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+/// Packs bits into bytes the way deflate does, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= u64::from(value) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are sent most significant bit first.
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW: usize = 32 * 1024;
+/// How many earlier positions are tried for each match, trading speed for
+/// size.
+const MAX_CHAIN: usize = 64;
+
+/// Writes a literal byte or length symbol with the fixed Huffman code.
+fn write_symbol(out: &mut BitWriter, symbol: u16) {
+    let symbol = u32::from(symbol);
+    match symbol {
+        0..=143 => out.write_code(0x30 + symbol, 8),
+        144..=255 => out.write_code(0x190 + symbol - 144, 9),
+        256..=279 => out.write_code(symbol - 256, 7),
+        _ => out.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+/// The code for `value` in a table of base values, with what is left over
+/// for the extra bits.
+fn base_code(bases: &[u16], value: usize) -> (usize, u32) {
+    let code = bases.partition_point(|&base| usize::from(base) <= value) - 1;
+    (code, (value - usize::from(bases[code])) as u32)
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize) {
+    let (code, extra) = base_code(&LENGTH_BASES, length);
+    write_symbol(out, 257 + code as u16);
+    out.write(extra, u32::from(LENGTH_EXTRA_BITS[code]));
+    let (code, extra) = base_code(&DISTANCE_BASES, distance);
+    out.write_code(code as u32, 5);
+    out.write(extra, u32::from(DISTANCE_EXTRA_BITS[code]));
+}
+
+/// Where each run of three bytes was seen before, most recent first: `head`
+/// holds the last position of each hash, `previous` the one before each
+/// position.
+struct Chains {
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl Chains {
+    const HASH_BITS: u32 = 15;
+
+    fn new(len: usize) -> Self {
+        Chains {
+            head: vec![usize::MAX; 1 << Self::HASH_BITS],
+            previous: vec![usize::MAX; len],
+        }
+    }
+
+    fn hash(data: &[u8], at: usize) -> usize {
+        let key =
+            u32::from(data[at]) << 16 | u32::from(data[at + 1]) << 8 | u32::from(data[at + 2]);
+        (key.wrapping_mul(2_654_435_761) >> (32 - Self::HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data: &[u8], at: usize) {
+        if at + MIN_MATCH <= data.len() {
+            let key = Self::hash(data, at);
+            self.previous[at] = self.head[key];
+            self.head[key] = at;
+        }
+    }
+
+    /// The longest earlier repeat of what starts at `at`, as its length and
+    /// distance back.
+    fn longest_match(&self, data: &[u8], at: usize) -> (usize, usize) {
+        let mut best = (0, 0);
+        if at + MIN_MATCH > data.len() {
+            return best;
+        }
+        let longest = (data.len() - at).min(MAX_MATCH);
+        let mut candidate = self.head[Self::hash(data, at)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || at - candidate > WINDOW {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[at..at + longest])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, at - candidate);
+                if length == longest {
+                    break;
+                }
+            }
+            candidate = self.previous[candidate];
+        }
+        best
+    }
+}
+
+/// Compresses `data` into a single deflate block with the fixed Huffman
+/// codes. Html repeats itself enough that finding the repeats gets most of
+/// what a tuned encoder would.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut chains = Chains::new(data.len());
+    let mut out = BitWriter::default();
+    // The last block, with fixed codes
+    out.write(1, 1);
+    out.write(1, 2);
+    let mut at = 0;
+    while at < data.len() {
+        let (length, distance) = chains.longest_match(data, at);
+        if length >= MIN_MATCH {
+            write_match(&mut out, length, distance);
+            for position in at..at + length {
+                chains.insert(data, position);
+            }
+            at += length;
+        } else {
+            write_symbol(&mut out, u16::from(data[at]));
+            chains.insert(data, at);
+            at += 1;
+        }
+    }
+    write_symbol(&mut out, 256);
+    out.finish()
+}
+
+/// The files below `folder`, sorted so the archive doesn't depend on the
+/// order the file system lists them in.
+fn files(folder: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Packs `folder` into a zip at `archive`, with its files below a `prefix`
+/// folder. Entries are deflated, or stored as they are when that doesn't
+/// make them smaller.
+pub(crate) fn zip_folder(folder: &Path, prefix: &str, archive: &Path) -> Result<(String, PathBuf)> {
+    if let Some(parent) = archive.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::create(archive)
+        .map_err(|e| anyhow!("Failed to create '{}': {}", archive.display(), e))?;
+    let mut out = BufWriter::new(file);
+    let table = crc_table();
+
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    for file in files(folder)? {
+        // Zip paths always use forward slashes
+        let name = std::iter::once(prefix.into())
+            .chain(
+                file.strip_prefix(folder)?
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy()),
+            )
+            .collect::<Vec<_>>()
+            .join("/");
+        let data = fs::read(&file)?;
+        let deflated = deflate(&data);
+        let (method, stored) = if deflated.len() < data.len() {
+            (DEFLATED, deflated)
+        } else {
+            (STORED, data.clone())
+        };
+        let entry = Entry {
+            crc: crc32(&table, &data),
+            method,
+            compressed_size: fits(stored.len(), "size of a file")?,
+            size: fits(data.len(), "size of a file")?,
+            offset: fits(offset, "archive")?,
+            name,
+        };
+        let mut header = 0x0403_4b50u32.to_le_bytes().to_vec();
+        entry.fields(&mut header)?;
+        header.extend(entry.name.as_bytes());
+        out.write_all(&header)?;
+        out.write_all(&stored)?;
+        offset += header.len() + stored.len();
+        entries.push(entry);
+    }
+
+    let mut directory = Vec::new();
+    for entry in &entries {
+        directory.extend(0x0201_4b50u32.to_le_bytes());
+        directory.extend(VERSION.to_le_bytes());
+        entry.fields(&mut directory)?;
+        // Comment length, disk number and file attributes
+        directory.extend([0; 10]);
+        directory.extend(entry.offset.to_le_bytes());
+        directory.extend(entry.name.as_bytes());
+    }
+    let count = fits::<u16>(entries.len(), "number of files")?;
+    let mut end = 0x0605_4b50u32.to_le_bytes().to_vec();
+    // Disk numbers
+    end.extend([0; 4]);
+    end.extend(count.to_le_bytes());
+    end.extend(count.to_le_bytes());
+    end.extend(fits::<u32>(directory.len(), "archive")?.to_le_bytes());
+    end.extend(fits::<u32>(offset, "archive")?.to_le_bytes());
+    // Comment length
+    end.extend([0; 2]);
+    out.write_all(&directory)?;
+    out.write_all(&end)?;
+    out.flush()?;
+
+    let archive = archive.canonicalize()?;
+    Ok((
+        format!("Packed {} files into {}", entries.len(), archive.display()),
+        archive,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    /// Reads deflate bits least significant first.
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        at: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |value, i| {
+                let bit = (self.bytes[self.at / 8] >> (self.at % 8)) & 1;
+                self.at += 1;
+                value | u32::from(bit) << i
+            })
+        }
+
+        fn code(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |code, _| code << 1 | self.bits(1))
+        }
+
+        /// A literal or length symbol in the fixed Huffman code.
+        fn symbol(&mut self) -> u16 {
+            let code = self.code(7);
+            if code <= 0x17 {
+                return (code + 256) as u16;
+            }
+            let code = code << 1 | self.bits(1);
+            match code {
+                0x30..=0xBF => (code - 0x30) as u16,
+                0xC0..=0xC7 => (code - 0xC0 + 280) as u16,
+                _ => ((code << 1 | self.bits(1)) - 0x190 + 144) as u16,
+            }
+        }
+    }
+
+    /// Decodes the single fixed Huffman block [`deflate`] writes.
+    fn inflate(bytes: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader { bytes, at: 0 };
+        assert_eq!(reader.bits(1), 1, "the last block");
+        assert_eq!(reader.bits(2), 1, "fixed codes");
+        let mut data = Vec::new();
+        loop {
+            let symbol = reader.symbol();
+            match symbol {
+                0..=255 => data.push(symbol as u8),
+                256 => return data,
+                _ => {
+                    let code = usize::from(symbol - 257);
+                    let length = usize::from(LENGTH_BASES[code])
+                        + reader.bits(u32::from(LENGTH_EXTRA_BITS[code])) as usize;
+                    let code = reader.code(5) as usize;
+                    let distance = usize::from(DISTANCE_BASES[code])
+                        + reader.bits(u32::from(DISTANCE_EXTRA_BITS[code])) as usize;
+                    for _ in 0..length {
+                        data.push(data[data.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deflates_what_inflates_back() {
+        let html: String = (0..5000)
+            .map(|i| {
+                format!(
+                    "<a class=\"el\" href=\"class{}.html\">Item {}</a>\n",
+                    i % 40,
+                    i
+                )
+            })
+            .collect();
+        let every_byte: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let inputs = [
+            Vec::new(),
+            b"a".to_vec(),
+            vec![b'x'; 70_000],
+            every_byte,
+            html.into_bytes(),
+        ];
+
+        for data in inputs {
+            let deflated = deflate(&data);
+            assert_eq!(inflate(&deflated), data);
+            if data.len() > 1000 {
+                assert!(deflated.len() < data.len() / 4, "{}", deflated.len());
+            }
+        }
+    }
+
+    #[test]
+    fn stores_files_deflating_would_not_shrink() {
+        let scratch = ScratchDir::new(false).unwrap();
+        let html = scratch.path().join("html");
+        fs::create_dir_all(html.join("search")).unwrap();
+        fs::write(html.join("index.html"), "<p>docs</p>\n".repeat(100)).unwrap();
+        fs::write(html.join("search").join("a.js"), "x").unwrap();
+        let archive = scratch.path().join("docs.zip");
+
+        zip_folder(&html, "html", &archive).unwrap();
+
+        let zip = fs::read(&archive).unwrap();
+        let field = |at: usize| u16::from_le_bytes([zip[at], zip[at + 1]]);
+        let name = |at: usize| {
+            let len = usize::from(field(at + 26));
+            String::from_utf8(zip[at + 30..at + 30 + len].to_vec()).unwrap()
+        };
+        assert_eq!(name(0), "html/index.html");
+        assert_eq!(field(8), DEFLATED);
+        let compressed = u32::from_le_bytes(zip[18..22].try_into().unwrap()) as usize;
+        let second = 30 + name(0).len() + compressed;
+        assert_eq!(name(second), "html/search/a.js");
+        assert_eq!(field(second + 8), STORED);
+        let start = 30 + name(0).len();
+        assert_eq!(
+            inflate(&zip[start..start + compressed]),
+            "<p>docs</p>\n".repeat(100).as_bytes()
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use std::io::{self, Write};
+
+use clap::{Arg, Command, ValueEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// A flag as the completion scripts need it.
+struct Flag {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    takes_value: bool,
+    /// The values it accepts, empty when any value (usually a path) goes.
+    values: Vec<String>,
+}
+
+impl Flag {
+    fn from_arg(arg: &Arg) -> Option<Self> {
+        if arg.is_positional() || arg.is_hide_set() {
+            return None;
+        }
+        Some(Flag {
+            long: arg.get_long().map(String::from),
+            short: arg.get_short(),
+            help: arg
+                .get_help()
+                .map(|help| help.to_string())
+                .unwrap_or_default()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            takes_value: arg.get_num_args().is_some_and(|n| n.takes_values()),
+            values: arg
+                .get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect(),
+        })
+    }
+
+    /// The spellings of the flag, e.g. `-h` and `--help`.
+    fn names(&self) -> Vec<String> {
+        let short = self.short.map(|short| format!("-{}", short));
+        let long = self.long.as_ref().map(|long| format!("--{}", long));
+        short.into_iter().chain(long).collect()
+    }
+}
+
+fn flags(cmd: &Command) -> Vec<Flag> {
+    cmd.get_arguments().filter_map(Flag::from_arg).collect()
+}
+
+/// The values the first positional argument accepts, e.g. the shells.
+fn positional_values(cmd: &Command) -> Vec<String> {
+    cmd.get_positionals()
+        .next()
+        .map(|arg| {
+            arg.get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn subcommands(cmd: &Command) -> Vec<&Command> {
+    cmd.get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .collect()
+}
+
+fn about(cmd: &Command) -> String {
+    cmd.get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default()
+}
+
+fn bash(cmd: &Command, bin: &str, out: &mut dyn Write) -> io::Result<()> {
+    let function = format!("_{}", bin.replace('-', "_"));
+    let names: Vec<&str> = subcommands(cmd).iter().map(|sub| sub.get_name()).collect();
+    writeln!(out, "{}() {{", function)?;
+    writeln!(out, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(out, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(out, "    local command=\"\" word")?;
+    writeln!(
+        out,
+        "    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do"
+    )?;
+    writeln!(out, "        case \"$word\" in")?;
+    writeln!(
+        out,
+        "            {}) command=\"$word\"; break ;;",
+        names.join("|")
+    )?;
+    writeln!(out, "        esac")?;
+    writeln!(out, "    done")?;
+    writeln!(out, "    case \"$command\" in")?;
+    let targets = subcommands(cmd)
+        .into_iter()
+        .map(|sub| (sub.get_name().to_string(), sub))
+        .chain([(String::from("*"), cmd)]);
+    for (pattern, target) in targets {
+        let flags = flags(target);
+        writeln!(out, "        {})", pattern)?;
+        writeln!(out, "            case \"$prev\" in")?;
+        for flag in flags.iter().filter(|flag| flag.takes_value) {
+            // An empty reply falls back to file completion through -o default
+            let reply = if flag.values.is_empty() {
+                String::from("return")
+            } else {
+                format!(
+                    "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return",
+                    flag.values.join(" ")
+                )
+            };
+            writeln!(
+                out,
+                "                {}) {} ;;",
+                flag.names().join("|"),
+                reply
+            )?;
+        }
+        writeln!(out, "            esac")?;
+        let mut words: Vec<String> = flags.iter().flat_map(Flag::names).collect();
+        if pattern == "*" {
+            words.extend(names.iter().map(|name| name.to_string()));
+        } else {
+            words.extend(positional_values(target));
+        }
+        writeln!(
+            out,
+            "            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+            words.join(" ")
+        )?;
+    }
+    writeln!(out, "    esac")?;
+    writeln!(out, "}}")?;
+    writeln!(out, "complete -o default -F {} {}", function, bin)
+}
+
+/// Escapes text for a single quoted `_arguments` spec.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_specs(cmd: &Command) -> Vec<String> {
+    flags(cmd)
+        .iter()
+        .flat_map(|flag| {
+            let help = zsh_escape(&flag.help);
+            let value = match (flag.takes_value, flag.values.is_empty()) {
+                (false, _) => String::new(),
+                (true, true) => String::from(":value:_files"),
+                (true, false) => format!(":value:({})", flag.values.join(" ")),
+            };
+            flag.names()
+                .into_iter()
+                .map(move |name| format!("'{}[{}]{}'", name, help, value))
+        })
+        .collect()
+}
+
+fn zsh(cmd: &Command, bin: &str, out: &mut dyn Write) -> io::Result<()> {
+    let function = format!("_{}", bin.replace('-', "_"));
+    writeln!(out, "#compdef {}", bin)?;
+    writeln!(out)?;
+    writeln!(out, "{}() {{", function)?;
+    writeln!(out, "    case ${{words[2]}} in")?;
+    for sub in subcommands(cmd) {
+        writeln!(out, "        {})", sub.get_name())?;
+        writeln!(out, "            shift words; (( CURRENT-- ))")?;
+        writeln!(out, "            _arguments \\")?;
+        for spec in zsh_specs(sub) {
+            writeln!(out, "                {} \\", spec)?;
+        }
+        let values = positional_values(sub);
+        if values.is_empty() {
+            writeln!(out, "                '*:file:_files'")?;
+        } else {
+            writeln!(out, "                '1:value:({})'", values.join(" "))?;
+        }
+        writeln!(out, "            return ;;")?;
+    }
+    writeln!(out, "    esac")?;
+    writeln!(out, "    local -a commands=(")?;
+    for sub in subcommands(cmd) {
+        writeln!(
+            out,
+            "        '{}:{}'",
+            sub.get_name(),
+            zsh_escape(&about(sub))
+        )?;
+    }
+    writeln!(out, "    )")?;
+    writeln!(out, "    _arguments \\")?;
+    for spec in zsh_specs(cmd) {
+        writeln!(out, "        {} \\", spec)?;
+    }
+    writeln!(out, "        '1: :{{_describe command commands; _files}}'")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "{} \"$@\"", function)
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish_flags(cmd: &Command, bin: &str, condition: &str, out: &mut dyn Write) -> io::Result<()> {
+    for flag in flags(cmd) {
+        let mut line = format!("complete -c {} -n \"{}\"", bin, condition);
+        if let Some(short) = flag.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        if let Some(long) = &flag.long {
+            line.push_str(&format!(" -l {}", long));
+        }
+        if flag.takes_value {
+            line.push_str(" -r");
+        }
+        if !flag.values.is_empty() {
+            line.push_str(&format!(" -f -a '{}'", flag.values.join(" ")));
+        }
+        line.push_str(&format!(" -d '{}'", fish_escape(&flag.help)));
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn fish(cmd: &Command, bin: &str, out: &mut dyn Write) -> io::Result<()> {
+    fish_flags(cmd, bin, "__fish_use_subcommand", out)?;
+    for sub in subcommands(cmd) {
+        writeln!(
+            out,
+            "complete -c {} -n \"__fish_use_subcommand\" -f -a {} -d '{}'",
+            bin,
+            sub.get_name(),
+            fish_escape(&about(sub))
+        )?;
+    }
+    for sub in subcommands(cmd) {
+        let condition = format!("__fish_seen_subcommand_from {}", sub.get_name());
+        let values = positional_values(sub);
+        if !values.is_empty() {
+            writeln!(
+                out,
+                "complete -c {} -n \"{}\" -f -a '{}'",
+                bin,
+                condition,
+                values.join(" ")
+            )?;
+        }
+        fish_flags(sub, bin, &condition, out)?;
+    }
+    Ok(())
+}
+
+fn powershell_escape(text: &str) -> String {
+    text.replace('\'', "''")
+}
+
+fn powershell_results(cmd: &Command, out: &mut dyn Write) -> io::Result<()> {
+    for flag in flags(cmd) {
+        for name in flag.names() {
+            writeln!(
+                out,
+                "            [CompletionResult]::new('{}', '{}', [CompletionResultType]::ParameterName, '{}')",
+                name,
+                name.trim_start_matches('-'),
+                powershell_escape(&flag.help)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn powershell(cmd: &Command, bin: &str, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "using namespace System.Management.Automation")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{",
+        bin
+    )?;
+    writeln!(
+        out,
+        "    param($wordToComplete, $commandAst, $cursorPosition)"
+    )?;
+    writeln!(
+        out,
+        "    $words = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})"
+    )?;
+    writeln!(out, "    $completions = switch ($words[1]) {{")?;
+    for sub in subcommands(cmd) {
+        writeln!(out, "        '{}' {{ @(", sub.get_name())?;
+        for value in positional_values(sub) {
+            writeln!(
+                out,
+                "            [CompletionResult]::new('{0}', '{0}', [CompletionResultType]::ParameterValue, '{0}')",
+                value
+            )?;
+        }
+        powershell_results(sub, out)?;
+        writeln!(out, "        ) }}")?;
+    }
+    writeln!(out, "        default {{ @(")?;
+    for sub in subcommands(cmd) {
+        writeln!(
+            out,
+            "            [CompletionResult]::new('{0}', '{0}', [CompletionResultType]::ParameterValue, '{1}')",
+            sub.get_name(),
+            powershell_escape(&about(sub))
+        )?;
+    }
+    powershell_results(cmd, out)?;
+    writeln!(out, "        ) }}")?;
+    writeln!(out, "    }}")?;
+    writeln!(
+        out,
+        "    $completions | Where-Object {{ $_.CompletionText -like \"$wordToComplete*\" }}"
+    )?;
+    writeln!(out, "}}")
+}
+
+/// Writes a script completing the flags and subcommands of `cmd` in `shell`.
+pub fn completions(shell: Shell, cmd: &mut Command, out: &mut dyn Write) -> io::Result<()> {
+    // Building adds the generated --help flags and help subcommand
+    cmd.build();
+    let bin = cmd.get_name().to_string();
+    match shell {
+        Shell::Bash => bash(cmd, &bin, out),
+        Shell::Zsh => zsh(cmd, &bin, out),
+        Shell::Fish => fish(cmd, &bin, out),
+        Shell::Powershell => powershell(cmd, &bin, out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("tool")
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .help("Output format")
+                    .value_parser(["html", "latex"]),
+            )
+            .arg(
+                Arg::new("quiet")
+                    .short('q')
+                    .long("quiet")
+                    .help("Say less")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(Arg::new("secret").long("secret").hide(true))
+            .subcommand(Command::new("doctor").about("Check the tools"))
+    }
+
+    #[test]
+    fn completes_flags_values_and_subcommands_in_every_shell() {
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            completions(*shell, &mut command(), &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+
+            let mut expected = vec!["tool", "format", "quiet", "doctor"];
+            // The PowerShell script completes flag names but not their values
+            if *shell != Shell::Powershell {
+                expected.extend(["html", "latex"]);
+            }
+            for expected in expected {
+                assert!(
+                    script.contains(expected),
+                    "{:?} completions lack {}:\n{}",
+                    shell,
+                    expected,
+                    script
+                );
+            }
+            assert!(!script.contains("secret"), "{:?}", shell);
+        }
+    }
+}
@@ -0,0 +1,1195 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::{
+    error::Error,
+    process::{as_strs, checked_output, print_command, stderr_tail, CommandRunner},
+};
+
+/// What `conan inspect` reports about a package.
+#[derive(Debug, Clone)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    pub requires: Vec<Requirement>,
+    /// The `build_requires` and `tool_requires`, only needed to build it.
+    pub build_requires: Vec<Requirement>,
+    /// The recipes its recipe reuses code from.
+    pub python_requires: Vec<Requirement>,
+    /// Empty when the recipe doesn't set them.
+    pub license: String,
+    pub description: String,
+    pub homepage: String,
+    pub url: String,
+    pub topics: Vec<String>,
+}
+
+impl PackageInfo {
+    /// A package nothing is known about but its name and version.
+    pub(crate) fn bare(name: String, version: String) -> Self {
+        PackageInfo {
+            name,
+            version,
+            requires: Vec::new(),
+            build_requires: Vec::new(),
+            python_requires: Vec::new(),
+            license: String::new(),
+            description: String::new(),
+            homepage: String::new(),
+            url: String::new(),
+            topics: Vec::new(),
+        }
+    }
+}
+
+/// A dependency of a package, e.g. `fmt/9.1.0` or `zlib/[>=1.2 <2]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requirement {
+    pub name: String,
+    /// The version or version range, without the user and channel.
+    pub version: String,
+}
+
+impl Requirement {
+    pub(crate) fn parse(reference: &str) -> Self {
+        let (name, rest) = reference.split_once('/').unwrap_or((reference, ""));
+        // Version ranges are bracketed and may contain spaces and @
+        let end = if rest.starts_with('[') {
+            rest.find(']').map_or(rest.len(), |i| i + 1)
+        } else {
+            rest.find(['@', '#']).unwrap_or(rest.len())
+        };
+        Requirement {
+            name: name.trim().to_string(),
+            version: rest[..end].trim().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.name, self.version)
+    }
+}
+
+/// Parses the Python literal conan prints for `requires`: a list or tuple of
+/// quoted references, or a bare reference.
+fn parse_requires(raw: &str) -> Vec<Requirement> {
+    // Packages without dependencies print `None`, `[]` or nothing at all
+    let raw = raw.trim();
+    if raw.is_empty() || raw == "None" {
+        return Vec::new();
+    }
+    let mut references = Vec::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c == '\'' || c == '"' {
+            references.push(chars.by_ref().take_while(|&q| q != c).collect::<String>());
+        }
+    }
+    if references.is_empty() {
+        references = raw
+            .trim_matches(['[', ']', '(', ')'])
+            .split(',')
+            .map(|reference| reference.trim().to_string())
+            .filter(|reference| !reference.is_empty())
+            .collect();
+    }
+    references
+        .iter()
+        .map(|reference| Requirement::parse(reference))
+        .collect()
+}
+
+/// How conan should configure the package graph when installing it.
+pub(crate) struct InstallOptions<'a> {
+    pub(crate) profile: &'a str,
+    pub(crate) installer: Option<&'a str>,
+    pub(crate) build_specs: &'a [String],
+    pub(crate) settings: &'a [String],
+    pub(crate) options: &'a [String],
+    pub(crate) install_folder: &'a Path,
+    pub(crate) timeout: Option<Duration>,
+    /// Install even when everything is already in the cache.
+    pub(crate) force: bool,
+    /// Run `conan source` for a local recipe without a `sources` folder.
+    pub(crate) fetch_sources: bool,
+    /// Where to save conan install's output.
+    pub(crate) install_log: Option<&'a Path>,
+    /// Lockfile pinning the graph, for install and for gathering sources.
+    pub(crate) lockfile: Option<&'a Path>,
+}
+
+/// Saves conan install's output to `log`, stdout first, then stderr.
+fn write_install_log(log: &Path, stdout: &[u8], stderr: &[u8]) -> Result<()> {
+    fs::write(log, [stdout, stderr].concat())
+        .map_err(|e| anyhow!("Failed to write the install log '{}': {}", log.display(), e))
+}
+
+/// Whether `src` names a conan reference such as `fmt/9.1.0@` or
+/// `fmt/9.1.0@user/channel` rather than a package folder on disk.
+pub(crate) fn is_reference(src: &str) -> bool {
+    if Path::new(src).exists() {
+        return false;
+    }
+
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._+-".contains(c))
+    };
+    let pair = |s: &str| s.split_once('/').is_some_and(|(a, b)| valid(a) && valid(b));
+
+    let (name_version, user_channel) = src.split_once('@').unwrap_or((src, ""));
+    pair(name_version) && (user_channel.is_empty() || pair(user_channel))
+}
+
+/// What conan 2 commands that resolve a graph are given for `src_pkg`: the
+/// folder of a local recipe, or `--requires` for a reference, as they only
+/// take paths as positional arguments. Conan 2 references don't end in `@`.
+fn v2_target(src_pkg: &str) -> String {
+    if is_reference(src_pkg) {
+        format!("--requires={}", src_pkg.trim_end_matches('@'))
+    } else {
+        src_pkg.to_string()
+    }
+}
+
+/// The full conan version, e.g. "1.62.0".
+pub(crate) fn conan_version(runner: &dyn CommandRunner, conan: &str) -> Result<String> {
+    let version_output_raw = checked_output(runner, conan, &["--version"], None)?.stdout;
+
+    // e.g. "Conan version 1.62.0"
+    let version_str = String::from_utf8(version_output_raw)?;
+    version_str
+        .split_whitespace()
+        .last()
+        .map(|v| v.to_string())
+        .ok_or(anyhow!(
+            "Failed to detect conan version from '{}'",
+            version_str.trim()
+        ))
+}
+
+pub(crate) fn conan_major_version(runner: &dyn CommandRunner, conan: &str) -> Result<u32> {
+    let version = conan_version(runner, conan)?;
+    version
+        .split('.')
+        .next()
+        .and_then(|major| major.parse().ok())
+        .ok_or(anyhow!("Failed to detect conan version from '{}'", version))
+}
+
+/// The package folders in a conan graph, each with the reference of the node
+/// it belongs to. Conan 1 calls the reference `reference`, conan 2 `ref`.
+/// Editable packages are documented from their working copy instead.
+fn package_folders(nodes: &[Value]) -> Vec<(String, String)> {
+    nodes
+        .iter()
+        .filter_map(|node| {
+            let folder = if is_editable(node) {
+                editable_folder(node)?
+            } else {
+                node.get("package_folder")?.as_str()?.to_string()
+            };
+            Some((node_reference(node).to_string(), folder))
+        })
+        .collect()
+}
+
+/// Whether conan resolved the node to a package put in editable mode with
+/// `conan editable add`, which both conan 1 and 2 report as its recipe.
+fn is_editable(node: &Value) -> bool {
+    ["recipe", "binary"]
+        .iter()
+        .any(|key| node.get(key).and_then(Value::as_str) == Some("Editable"))
+}
+
+/// Where an editable package's sources are. Its layout may name a source
+/// folder, otherwise they are taken from the folder of its conanfile, which
+/// conan 1 reports as the package folder. Like local recipes, a `sources`
+/// folder there is preferred.
+fn editable_folder(node: &Value) -> Option<String> {
+    let folder = ["source_folder", "recipe_folder", "package_folder"]
+        .iter()
+        .filter_map(|key| node.get(key)?.as_str())
+        .find(|folder| Path::new(folder).is_dir())?;
+    let sources = Path::new(folder).join("sources");
+    if sources.is_dir() {
+        Some(sources.display().to_string())
+    } else {
+        Some(folder.to_string())
+    }
+}
+
+fn node_reference(node: &Value) -> &str {
+    ["reference", "ref"]
+        .iter()
+        .find_map(|key| node.get(key)?.as_str())
+        .unwrap_or_default()
+}
+
+/// Whether every package in the graph is already in the conan cache, so
+/// there is nothing for conan install to fetch. The local recipe itself,
+/// reported as `conanfile.py` or without a reference, and editable packages
+/// need no package.
+fn all_installed(nodes: &[Value]) -> bool {
+    nodes
+        .iter()
+        .filter(|node| {
+            let reference = node_reference(node);
+            !reference.is_empty() && !reference.starts_with("conanfile") && !is_editable(node)
+        })
+        .all(|node| {
+            node.get("package_folder")
+                .and_then(Value::as_str)
+                .is_some_and(|folder| Path::new(folder).is_dir())
+        })
+}
+
+/// The nodes of the dependency graph of `src_pkg`, from `conan info` in
+/// conan 1 and `conan graph info` in conan 2.
+fn graph_nodes(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    install_options: &InstallOptions,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<Vec<Value>> {
+    if conan_major_version(runner, conan)? >= 2 {
+        graph_info_nodes(runner, conan, src_pkg, install_options, verbose, dry_run)
+    } else {
+        info_nodes(runner, conan, src_pkg, install_options, verbose, dry_run)
+    }
+}
+
+fn info_nodes(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    install_options: &InstallOptions,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<Vec<Value>> {
+    let lockfile_args = lockfile_args(install_options)?;
+    let mut args = vec!["info", src_pkg, "--paths", "--json"];
+    args.extend(as_strs(&lockfile_args));
+    if dry_run {
+        print_command(conan, &args);
+        return Ok(Vec::new());
+    }
+
+    let info_output_raw = checked_output(runner, conan, &args, install_options.timeout)?.stdout;
+
+    let info_output_raw_str = String::from_utf8(info_output_raw)?;
+    if verbose {
+        println!("{}", info_output_raw_str);
+    }
+
+    // The JSON is printed on a single line, but conan may print banners and
+    // warnings before or after it
+    let info_json_obj: Vec<Value> = info_output_raw_str
+        .lines()
+        .find_map(|line| serde_json::from_str(line.trim()).ok())
+        .ok_or(Error::ConanInfoParse)?;
+    Ok(info_json_obj)
+}
+
+fn graph_info_nodes(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    install_options: &InstallOptions,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<Vec<Value>> {
+    let target = v2_target(src_pkg);
+    let mut args = vec!["graph", "info", &target, "--format", "json"];
+    let configuration_args = configuration_args(install_options, true)?;
+    args.extend(as_strs(&configuration_args));
+    if dry_run {
+        print_command(conan, &args);
+        return Ok(Vec::new());
+    }
+
+    let graph_output_raw = checked_output(runner, conan, &args, install_options.timeout)?.stdout;
+
+    if verbose {
+        println!("{}", String::from_utf8_lossy(&graph_output_raw));
+    }
+
+    let graph_json_obj: Value =
+        serde_json::from_slice(&graph_output_raw).map_err(|_| Error::ConanInfoParse)?;
+
+    // nodes is keyed by node id in recent 2.x releases, and a list in early ones
+    match graph_json_obj.pointer("/graph/nodes") {
+        Some(Value::Object(nodes)) => Ok(nodes.values().cloned().collect()),
+        Some(Value::Array(nodes)) => Ok(nodes.clone()),
+        _ => Err(Error::ConanInfoParse.into()),
+    }
+}
+
+/// Drops folders that are listed more than once, or that sit inside another
+/// listed folder, so doxygen scans each file once. The order is kept.
+fn dedupe_folders(folders: Vec<String>) -> Vec<String> {
+    let resolved: Vec<PathBuf> = folders
+        .iter()
+        .map(|folder| {
+            Path::new(folder)
+                .canonicalize()
+                .unwrap_or_else(|_| PathBuf::from(folder))
+        })
+        .collect();
+    folders
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            let path = &resolved[*i];
+            !resolved.iter().enumerate().any(|(j, other)| {
+                // Of two identical paths only the first is kept
+                (other == path && j < *i) || (other != path && path.starts_with(other))
+            })
+        })
+        .map(|(_, folder)| folder)
+        .collect()
+}
+
+/// The name in a reference such as `fmt/9.1.0@user/channel#rev`.
+fn reference_name(reference: &str) -> &str {
+    reference.split('/').next().unwrap_or_default()
+}
+
+/// The nodes a node depends on directly. Conan 1 lists their references in
+/// `requires`, conan 2 their ids in `dependencies`, along with transitive
+/// ones marked as not `direct`.
+fn node_requires(nodes: &[Value], node: &Value) -> Vec<usize> {
+    let id = |node: &Value| match node.get("id") {
+        Some(Value::String(id)) => Some(id.clone()),
+        Some(Value::Number(id)) => Some(id.to_string()),
+        _ => None,
+    };
+    if let Some(Value::Object(dependencies)) = node.get("dependencies") {
+        return dependencies
+            .iter()
+            .filter(|(_, dependency)| dependency.get("direct") != Some(&Value::Bool(false)))
+            .filter_map(|(dep_id, _)| {
+                nodes
+                    .iter()
+                    .position(|other| id(other).as_ref() == Some(dep_id))
+            })
+            .collect();
+    }
+    let requires = node.get("requires").and_then(Value::as_array);
+    requires
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter_map(|reference| {
+            nodes
+                .iter()
+                .position(|other| node_reference(other) == reference)
+        })
+        .collect()
+}
+
+/// Keeps the nodes at most `depth` steps away from the package in the graph:
+/// 0 is the package itself, 1 adds its direct dependencies and so on. The
+/// package is the node named like a reference `src_pkg`, or for a local
+/// recipe whatever nothing depends on.
+fn within_depth(nodes: Vec<Value>, src_pkg: &str, depth: usize) -> Vec<Value> {
+    let requires: Vec<Vec<usize>> = nodes
+        .iter()
+        .map(|node| node_requires(&nodes, node))
+        .collect();
+    let mut pending: Vec<usize> = if is_reference(src_pkg) {
+        (0..nodes.len())
+            .filter(|&i| reference_name(node_reference(&nodes[i])) == reference_name(src_pkg))
+            .collect()
+    } else {
+        (0..nodes.len())
+            .filter(|i| !requires.iter().flatten().any(|required| required == i))
+            .collect()
+    };
+
+    let mut distance = vec![None; nodes.len()];
+    for &root in &pending {
+        distance[root] = Some(0);
+    }
+    // Breadth first, so each node gets its shortest distance
+    while !pending.is_empty() {
+        let mut next = Vec::new();
+        for node in pending {
+            let steps = distance[node].unwrap_or_default() + 1;
+            for &required in &requires[node] {
+                if distance[required].is_none() {
+                    distance[required] = Some(steps);
+                    next.push(required);
+                }
+            }
+        }
+        pending = next;
+    }
+
+    nodes
+        .into_iter()
+        .zip(distance)
+        .filter(|(_, distance)| distance.is_some_and(|distance| distance <= depth))
+        .map(|(node, _)| node)
+        .collect()
+}
+
+/// Collects the folders doxygen should scan. With a `depth` only the packages
+/// that close to the package in the graph are kept, 0 being just its own
+/// sources.
+pub(crate) fn gather_sources(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    install_options: &InstallOptions,
+    depth: Option<usize>,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<(String, Vec<String>)> {
+    let reference = is_reference(src_pkg);
+    let package_folders = if depth == Some(0) && !reference {
+        // A local recipe's own sources don't come from the graph
+        Vec::new()
+    } else {
+        let nodes = graph_nodes(runner, conan, src_pkg, install_options, verbose, dry_run)?;
+        match depth {
+            Some(depth) => package_folders(&within_depth(nodes, src_pkg, depth)),
+            None => package_folders(&nodes),
+        }
+    };
+    let mut source_folders: Vec<String> = package_folders
+        .into_iter()
+        .map(|(_, folder)| folder)
+        .collect();
+
+    // conan info already reports the package folder of a reference, while a
+    // local recipe keeps its sources next to the conanfile
+    if !reference {
+        let sources = Path::new(src_pkg).join("sources").display().to_string();
+        if install_options.fetch_sources && !Path::new(&sources).is_dir() {
+            source_folders.push(conan_source(
+                runner,
+                conan,
+                src_pkg,
+                install_options,
+                verbose,
+                dry_run,
+            )?);
+        } else {
+            source_folders.push(sources);
+        }
+    }
+    if dry_run {
+        return Ok((
+            format!("Found {} source locations", source_folders.len()),
+            source_folders,
+        ));
+    }
+
+    // Doxygen quietly produces an empty site for folders that don't exist
+    let found = source_folders.len();
+    source_folders.retain(|folder| Path::new(folder).is_dir());
+    if source_folders.is_empty() {
+        return Err(Error::NoSourcesFound {
+            src: src_pkg.to_string(),
+        }
+        .into());
+    }
+    let missing = found - source_folders.len();
+    let source_folders = dedupe_folders(source_folders);
+    let msg = if missing > 0 {
+        format!(
+            "Found {} source locations, skipped {} that don't exist",
+            source_folders.len(),
+            missing
+        )
+    } else {
+        format!("Found {} source locations", source_folders.len())
+    };
+    Ok((msg, source_folders))
+}
+
+/// Fetches a local recipe's sources with `conan source`, returning where they
+/// went. Conan 1 is told to put them in the `sources` folder, so later runs
+/// find them without fetching again. Conan 2 has no such option and follows
+/// the recipe's layout, which puts them next to the conanfile unless it says
+/// otherwise.
+fn conan_source(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    install_options: &InstallOptions,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<String> {
+    let sources = Path::new(src_pkg).join("sources").display().to_string();
+    let conan_v2 = !dry_run && conan_major_version(runner, conan)? >= 2;
+    let mut args = vec!["source", src_pkg];
+    if !conan_v2 {
+        args.extend(["-sf", &sources]);
+    }
+    if verbose || dry_run {
+        print_command(conan, &args);
+    }
+    if dry_run {
+        return Ok(sources);
+    }
+
+    let output = runner.run_timeout(conan, &args, install_options.timeout)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "conan source failed ({}):\n{}",
+            output.status,
+            stderr_tail(&output.stderr)
+        ));
+    }
+    if conan_v2 && !Path::new(&sources).is_dir() {
+        return Ok(src_pkg.to_string());
+    }
+    Ok(sources)
+}
+
+/// Profile files are passed to conan as absolute paths so they resolve
+/// regardless of where conan looks for named profiles.
+fn resolve_profile(profile: &str) -> Result<String> {
+    let path = Path::new(profile);
+    if !path.is_file() {
+        return Ok(profile.to_string());
+    }
+
+    path.canonicalize()?
+        .to_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("Failed to convert profile path to str"))
+}
+
+fn lockfile_args(install_options: &InstallOptions) -> Result<Vec<String>> {
+    let Some(lockfile) = install_options.lockfile else {
+        return Ok(Vec::new());
+    };
+    let lockfile = lockfile
+        .to_str()
+        .ok_or_else(|| anyhow!("Failed to convert lockfile path to str"))?;
+    Ok(vec![String::from("--lockfile"), lockfile.to_string()])
+}
+
+/// The lockfile, profile, settings and options arguments shared by the conan
+/// commands that resolve the package graph. Conan 1 takes the configuration
+/// from the lockfile and refuses any other, so it only gets the lockfile.
+fn configuration_args(install_options: &InstallOptions, conan_v2: bool) -> Result<Vec<String>> {
+    let mut args = lockfile_args(install_options)?;
+    if install_options.lockfile.is_some() && !conan_v2 {
+        return Ok(args);
+    }
+    args.extend([
+        String::from("-pr"),
+        resolve_profile(install_options.profile)?,
+    ]);
+    for setting in install_options.settings {
+        args.push(String::from("-s"));
+        args.push(setting.clone());
+    }
+    for option in install_options.options {
+        args.push(String::from("-o"));
+        args.push(option.clone());
+    }
+    Ok(args)
+}
+
+/// The arguments of `conan install`, which conan 1 and conan 2 spell
+/// differently. References are installed straight into the conan cache.
+fn install_args(
+    src_pkg: &str,
+    install_options: &InstallOptions,
+    conan_v2: bool,
+) -> Result<Vec<String>> {
+    let target = if conan_v2 {
+        v2_target(src_pkg)
+    } else {
+        src_pkg.to_string()
+    };
+    let mut args = vec![String::from("install"), target];
+    if !is_reference(src_pkg) {
+        let install_folder = install_options
+            .install_folder
+            .to_str()
+            .ok_or_else(|| anyhow!("Failed to convert install folder to str"))?;
+        // Conan 2 dropped --install-folder for --output-folder
+        let flag = if conan_v2 { "-of" } else { "-if" };
+        args.extend([flag, install_folder].map(String::from));
+    }
+    args.extend(configuration_args(install_options, conan_v2)?);
+    args.extend(
+        install_options
+            .build_specs
+            .iter()
+            .map(|spec| format!("--build={}", spec)),
+    );
+    Ok(args)
+}
+
+static INSTALL_LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) fn conan_install(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    install_options: &InstallOptions,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<(String, ())> {
+    let (program, mut args) = match install_options.installer {
+        Some(installer) => (installer, vec![conan.to_string()]),
+        None => (conan, Vec::new()),
+    };
+    let conan_v2 = conan_major_version(runner, conan)? >= 2;
+    args.extend(install_args(src_pkg, install_options, conan_v2)?);
+    let args = as_strs(&args);
+    if dry_run {
+        print_command(program, &args);
+        return Ok((String::from("Skipped conan install (dry run)"), ()));
+    }
+
+    // Concurrent installs into the same conan cache can corrupt it
+    let _lock = INSTALL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // Asking the graph is much quicker than an install with nothing to do.
+    // When asking fails, install anyway and let it report the problem
+    if !install_options.force
+        && graph_nodes(runner, conan, src_pkg, install_options, false, false)
+            .is_ok_and(|nodes| all_installed(&nodes))
+    {
+        let msg = String::from("Packages already installed.");
+        if let Some(log) = install_options.install_log {
+            write_install_log(log, format!("{}\n", msg).as_bytes(), &[])?;
+        }
+        return Ok((msg, ()));
+    }
+    // The output has to be captured to be saved, it is echoed afterwards
+    if verbose && install_options.install_log.is_none() {
+        let status = runner.stream_timeout(program, &args, install_options.timeout)?;
+        if !status.success() {
+            return Err(Error::ConanInstallFailed {
+                status,
+                stderr: String::new(),
+            }
+            .into());
+        }
+    } else {
+        let output = runner.run_timeout(program, &args, install_options.timeout)?;
+        if let Some(log) = install_options.install_log {
+            write_install_log(log, &output.stdout, &output.stderr)?;
+        }
+        if verbose {
+            print!("{}", String::from_utf8_lossy(&output.stdout));
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+        if !output.status.success() {
+            return Err(Error::ConanInstallFailed {
+                status: output.status,
+                stderr: stderr_tail(&output.stderr),
+            }
+            .into());
+        }
+    }
+    Ok((String::from("Finished conan install"), ()))
+}
+
+fn inspect_field(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    field: &str,
+    timeout: Option<Duration>,
+    verbose: bool,
+) -> Result<String> {
+    let args = ["inspect", src_pkg, "--raw", field];
+    let field_bytes = checked_output(runner, conan, &args, timeout)?.stdout;
+
+    // conan ends the raw value with a newline, which would otherwise end up in
+    // PROJECT_NAME and the default output folder
+    let field_str = String::from_utf8(field_bytes)?.trim().to_string();
+    if verbose {
+        println!("{}: {}", field, field_str);
+    }
+    Ok(field_str)
+}
+
+/// conan prints Python's `None` for attributes a recipe leaves unset.
+fn unset_as_empty(value: String) -> String {
+    if value == "None" {
+        String::new()
+    } else {
+        value
+    }
+}
+
+/// Splits a Python tuple or list such as `('json', 'parser')` into its items.
+fn split_tuple(value: &str) -> Vec<String> {
+    value
+        .trim_start_matches(['(', '['])
+        .trim_end_matches([')', ']'])
+        .split(',')
+        .map(|s| s.trim().replace(['\'', '"'], ""))
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// The recipe attributes the docs use, in the order conan 1 is asked for them.
+const INSPECT_FIELDS: [&str; 11] = [
+    "name",
+    "version",
+    "requires",
+    "build_requires",
+    "tool_requires",
+    "python_requires",
+    "license",
+    "description",
+    "homepage",
+    "url",
+    "topics",
+];
+
+/// A JSON attribute as text, empty when the recipe doesn't set it.
+fn json_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => unset_as_empty(text.clone()),
+        other => other.to_string(),
+    }
+}
+
+/// A JSON attribute holding a list, which recipes may also set to a single
+/// string. Conan 2 lists requirements as objects carrying their `ref`.
+fn json_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| match item {
+                Value::String(text) => Some(text.clone()),
+                item => ["ref", "reference"]
+                    .iter()
+                    .find_map(|key| item.get(key)?.as_str())
+                    .map(String::from),
+            })
+            .collect(),
+        Value::Object(items) => items.keys().cloned().collect(),
+        Value::String(text) => split_tuple(&unset_as_empty(text.clone())),
+        _ => Vec::new(),
+    }
+}
+
+/// The arguments asking conan for all of [`INSPECT_FIELDS`] as JSON: conan 2
+/// prints it with `--format json`, conan 1 writes it to `json_file`.
+fn inspect_json_args<'a>(src_pkg: &'a str, json_file: &'a str, conan_v2: bool) -> Vec<&'a str> {
+    let mut args = vec!["inspect", src_pkg];
+    if conan_v2 {
+        args.extend(["--format", "json"]);
+    } else {
+        args.extend(["--json", json_file]);
+        for field in INSPECT_FIELDS {
+            args.extend(["-a", field]);
+        }
+    }
+    args
+}
+
+/// Reads the recipe attributes with a single `conan inspect`. `None` when
+/// conan can't produce JSON, e.g. before conan 1.18.
+fn inspect_json(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    json_file: &Path,
+    conan_v2: bool,
+    timeout: Option<Duration>,
+) -> Result<Option<PackageInfo>> {
+    let Some(json_file_str) = json_file.to_str() else {
+        return Ok(None);
+    };
+    let args = inspect_json_args(src_pkg, json_file_str, conan_v2);
+    // Running out of time is an error, raw inspection would run out as well
+    let output = runner.run_timeout(conan, &args, timeout)?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let json = if conan_v2 {
+        String::from_utf8_lossy(&output.stdout).to_string()
+    } else {
+        match fs::read_to_string(json_file) {
+            Ok(json) => json,
+            Err(_) => return Ok(None),
+        }
+    };
+    let Ok(attributes) = serde_json::from_str::<Value>(&json) else {
+        return Ok(None);
+    };
+    let requirements = |field: &str| match &attributes[field] {
+        Value::String(raw) => parse_requires(raw),
+        requires => json_list(requires)
+            .iter()
+            .map(|reference| Requirement::parse(reference))
+            .collect(),
+    };
+    Ok(Some(PackageInfo {
+        name: json_text(&attributes["name"]),
+        version: json_text(&attributes["version"]),
+        requires: requirements("requires"),
+        build_requires: merge_requires(
+            requirements("build_requires"),
+            requirements("tool_requires"),
+        ),
+        python_requires: requirements("python_requires"),
+        license: json_text(&attributes["license"]),
+        description: json_text(&attributes["description"]),
+        homepage: json_text(&attributes["homepage"]),
+        url: json_text(&attributes["url"]),
+        topics: json_list(&attributes["topics"]),
+    }))
+}
+
+/// Reads the recipe attributes one `conan inspect --raw` at a time, for conan
+/// versions without JSON output.
+fn inspect_raw(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    timeout: Option<Duration>,
+    verbose: bool,
+) -> Result<PackageInfo> {
+    let field = |field| inspect_field(runner, conan, src_pkg, field, timeout, verbose);
+    let optional = |name| field(name).map(unset_as_empty);
+    // Older conan 1 releases may not know tool_requires, which leaves it
+    // empty rather than failing the whole inspection
+    let requirements = |name| parse_requires(&field(name).unwrap_or_default());
+    Ok(PackageInfo {
+        name: field("name")?,
+        version: field("version")?,
+        requires: parse_requires(&field("requires")?),
+        build_requires: merge_requires(
+            requirements("build_requires"),
+            requirements("tool_requires"),
+        ),
+        python_requires: requirements("python_requires"),
+        license: optional("license")?,
+        description: optional("description")?,
+        homepage: optional("homepage")?,
+        url: optional("url")?,
+        topics: split_tuple(&optional("topics")?),
+    })
+}
+
+/// The name of the folder holding a local recipe.
+fn folder_name(src_pkg: &str) -> Result<String> {
+    Ok(Path::new(src_pkg)
+        .canonicalize()?
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("package")))
+}
+
+/// Conan 1 recipes may use both the older `build_requires` and `tool_requires`,
+/// which may list the same package.
+fn merge_requires(mut requires: Vec<Requirement>, more: Vec<Requirement>) -> Vec<Requirement> {
+    for requirement in more {
+        if !requires.contains(&requirement) {
+            requires.push(requirement);
+        }
+    }
+    requires
+}
+
+/// The references in a section of a conanfile.txt, e.g. `[requires]`.
+fn txt_requires(conanfile: &str, section: &str) -> Vec<Requirement> {
+    conanfile
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .skip_while(|line| *line != section)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .filter(|line| !line.is_empty())
+        .map(Requirement::parse)
+        .collect()
+}
+
+/// A conanfile.txt only lists dependencies, it has no name or version for
+/// conan inspect to report, so the folder stands in for the project.
+fn inspect_txt(src_pkg: &str) -> Result<Option<PackageInfo>> {
+    let src = Path::new(src_pkg);
+    let conanfile = src.join("conanfile.txt");
+    if is_reference(src_pkg) || src.join("conanfile.py").is_file() || !conanfile.is_file() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(&conanfile)
+        .map_err(|e| anyhow!("Failed to read '{}': {}", conanfile.display(), e))?;
+    Ok(Some(PackageInfo {
+        requires: txt_requires(&contents, "[requires]"),
+        build_requires: merge_requires(
+            txt_requires(&contents, "[build_requires]"),
+            txt_requires(&contents, "[tool_requires]"),
+        ),
+        ..PackageInfo::bare(folder_name(src_pkg)?, String::from("local"))
+    }))
+}
+
+/// Reads the recipe's attributes, writing conan 1's JSON to `json_file`.
+pub(crate) fn inspect(
+    runner: &dyn CommandRunner,
+    conan: &str,
+    src_pkg: &str,
+    json_file: &Path,
+    timeout: Option<Duration>,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<PackageInfo> {
+    if let Some(package) = inspect_txt(src_pkg)? {
+        return Ok(package);
+    }
+    let conan_v2 = conan_major_version(runner, conan).is_ok_and(|major| major >= 2);
+
+    // Conan 2 only inspects recipe files, so a reference has to speak for
+    // itself
+    if conan_v2 && is_reference(src_pkg) {
+        let reference = Requirement::parse(src_pkg);
+        if verbose {
+            println!("name: {}", reference.name);
+            println!("version: {}", reference.version);
+        }
+        return Ok(PackageInfo::bare(reference.name, reference.version));
+    }
+
+    // Stand-ins so the Doxyfile can still be generated and inspected
+    if dry_run {
+        let json_file = json_file.to_string_lossy();
+        print_command(conan, &inspect_json_args(src_pkg, &json_file, conan_v2));
+        let name = if is_reference(src_pkg) {
+            src_pkg.split('/').next().unwrap_or(src_pkg).to_string()
+        } else {
+            folder_name(src_pkg)?
+        };
+        return Ok(PackageInfo::bare(name, String::from("dry-run")));
+    }
+
+    let package = match inspect_json(runner, conan, src_pkg, json_file, conan_v2, timeout)? {
+        Some(package) => package,
+        None => inspect_raw(runner, conan, src_pkg, timeout, verbose)?,
+    };
+    if verbose {
+        println!("name: {}", package.name);
+        println!("version: {}", package.version);
+    }
+    Ok(package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeRunner;
+    use serde_json::json;
+
+    fn install_options(install_folder: &Path) -> InstallOptions<'_> {
+        InstallOptions {
+            profile: "default",
+            installer: None,
+            build_specs: &[],
+            settings: &[],
+            options: &[],
+            install_folder,
+            timeout: None,
+            force: false,
+            fetch_sources: false,
+            install_log: None,
+            lockfile: None,
+        }
+    }
+
+    #[test]
+    fn trims_the_newlines_conan_inspect_prints() {
+        let runner = FakeRunner::new(|_, args| match args {
+            [.., "name"] => (0, String::from("mypkg\n")),
+            [.., "version"] => (0, String::from("1.2.3\r\n")),
+            [.., "requires"] => (0, String::from("['fmt/9.1.0']\n")),
+            [.., "topics"] => (0, String::from("('json', 'parser')\n")),
+            _ => (0, String::from("None\n")),
+        });
+
+        let package = inspect_raw(&runner, "conan", "pkg", None, false).unwrap();
+
+        assert_eq!(package.name, "mypkg");
+        assert_eq!(package.version, "1.2.3");
+        assert_eq!(package.requires, [Requirement::parse("fmt/9.1.0")]);
+        assert_eq!(package.license, "");
+        assert_eq!(package.topics, ["json", "parser"]);
+    }
+
+    #[test]
+    fn finds_the_info_json_between_banners_and_warnings() {
+        let runner = FakeRunner::new(|_, _| {
+            let stdout = [
+                "Conan 1.62 is deprecated, please migrate",
+                r#"[{"reference": "mypkg/1.2.3", "source_folder": "/src/mypkg"}]"#,
+                "",
+                "WARN: remotes.json was updated",
+            ];
+            (0, stdout.join("\n"))
+        });
+        let install_folder = PathBuf::from("build");
+
+        let nodes = info_nodes(
+            &runner,
+            "conan",
+            "pkg",
+            &install_options(&install_folder),
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(node_reference(&nodes[0]), "mypkg/1.2.3");
+    }
+
+    #[test]
+    fn forwards_settings_and_options_to_conan() {
+        let settings = [String::from("compiler=gcc")];
+        let options = [String::from("mypkg:shared=True")];
+        let build_specs = [String::from("missing")];
+        let install_folder = PathBuf::from("build");
+        let install_options = InstallOptions {
+            settings: &settings,
+            options: &options,
+            build_specs: &build_specs,
+            ..install_options(&install_folder)
+        };
+        let configuration = "-pr default -s compiler=gcc -o mypkg:shared=True";
+
+        for (version, expected) in [
+            (
+                "1.62.0",
+                [
+                    String::from("conan info pkg --paths --json"),
+                    format!(
+                        "conan install pkg -if build {} --build=missing",
+                        configuration
+                    ),
+                ],
+            ),
+            (
+                "2.0.5",
+                [
+                    format!("conan graph info pkg --format json {}", configuration),
+                    format!(
+                        "conan install pkg -of build {} --build=missing",
+                        configuration
+                    ),
+                ],
+            ),
+        ] {
+            let runner = FakeRunner::new(|_, args| match args {
+                ["--version"] => (0, format!("Conan version {}\n", version)),
+                ["install", ..] => (0, String::new()),
+                // Unknown graph, so the install goes ahead
+                _ => (1, String::new()),
+            });
+
+            conan_install(&runner, "conan", "pkg", &install_options, false, false).unwrap();
+
+            let calls = runner.calls();
+            for command in expected {
+                assert!(calls.contains(&command), "{} not in {:?}", command, calls);
+            }
+        }
+    }
+
+    #[test]
+    fn drops_duplicate_and_nested_folders() {
+        let folders = [
+            "/nowhere/fmt/include",
+            "/nowhere/zlib",
+            "/nowhere/fmt/include",
+            "/nowhere/fmt",
+            "/nowhere/fmtlib",
+            "/nowhere/zlib/src",
+        ];
+
+        let folders = dedupe_folders(folders.map(String::from).to_vec());
+
+        assert_eq!(
+            folders,
+            ["/nowhere/zlib", "/nowhere/fmt", "/nowhere/fmtlib"]
+        );
+    }
+
+    #[test]
+    fn parses_requires_into_names_and_versions() {
+        let requirement = |name: &str, version: &str| Requirement {
+            name: name.to_string(),
+            version: version.to_string(),
+        };
+
+        assert_eq!(parse_requires("[]"), []);
+        assert_eq!(
+            parse_requires("fmt/9.1.0@user/channel"),
+            [requirement("fmt", "9.1.0")]
+        );
+        assert_eq!(
+            parse_requires("['fmt/9.1.0', \"zlib/[>=1.2 <2]@\", 'boost/[~1.81]#rev']"),
+            [
+                requirement("fmt", "9.1.0"),
+                requirement("zlib", "[>=1.2 <2]"),
+                requirement("boost", "[~1.81]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_unset_requires_as_no_requirements() {
+        for raw in ["None", "None\n", "", "()", "[ ]"] {
+            assert_eq!(parse_requires(raw), [], "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn keeps_the_nodes_within_depth() {
+        let conan_1 = vec![
+            json!({ "reference": "conanfile.py", "requires": ["spdlog/1.12.0"] }),
+            json!({ "reference": "spdlog/1.12.0", "requires": ["fmt/9.1.0"] }),
+            json!({ "reference": "fmt/9.1.0" }),
+        ];
+        // Conan 2 also lists fmt as a transitive dependency of the recipe
+        let conan_2 = vec![
+            json!({ "id": "0", "ref": "conanfile", "dependencies": {
+                "1": { "direct": true },
+                "2": { "direct": false },
+            } }),
+            json!({ "id": "1", "ref": "spdlog/1.12.0", "dependencies": { "2": { "direct": true } } }),
+            json!({ "id": "2", "ref": "fmt/9.1.0", "dependencies": {} }),
+        ];
+        let kept = |nodes: &[Value], src_pkg: &str, depth: usize| {
+            within_depth(nodes.to_vec(), src_pkg, depth)
+                .iter()
+                .map(|node| reference_name(node_reference(node)).to_string())
+                .collect::<Vec<String>>()
+        };
+
+        for nodes in [conan_1, conan_2] {
+            assert_eq!(kept(&nodes, "pkg", 0).len(), 1);
+            assert_eq!(kept(&nodes, "pkg", 1)[1..], ["spdlog"]);
+            assert_eq!(kept(&nodes, "pkg", 2)[1..], ["spdlog", "fmt"]);
+            assert_eq!(kept(&nodes, "spdlog/1.12.0@", 0), ["spdlog"]);
+            assert_eq!(kept(&nodes, "spdlog/1.12.0@", 1), ["spdlog", "fmt"]);
+        }
+    }
+}
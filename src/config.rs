@@ -0,0 +1,399 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use serde_json::{json, Value};
+
+use crate::{
+    conan::{is_reference, PackageInfo},
+    default_output,
+    doxygen::Format,
+    process::which,
+    GenerateOptions,
+};
+
+/// The config file picked up from the package folder when `--config` isn't given.
+const CONFIG_FILE: &str = ".conan-doxygen.toml";
+
+const KEYS: [&str; 7] = [
+    "profile",
+    "exclude",
+    "file_patterns",
+    "format",
+    "out",
+    "template",
+    "extract_all",
+];
+
+/// Parses a single TOML value: a string, a boolean, an integer or an array of
+/// those. Tables aren't needed for a flat settings file.
+fn parse_value(text: &str) -> Result<Value> {
+    let text = text.trim();
+    if let Some(items) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        let mut values = Vec::new();
+        let mut rest = items.trim();
+        while !rest.is_empty() {
+            let (item, tail) = split_item(rest)?;
+            values.push(parse_value(item)?);
+            rest = tail.trim_start().trim_start_matches(',').trim_start();
+        }
+        return Ok(Value::Array(values));
+    }
+    if let Some(literal) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        return Ok(Value::from(literal));
+    }
+    if text.starts_with('"') {
+        return serde_json::from_str(text).map_err(|e| anyhow!("invalid string {}: {}", text, e));
+    }
+    match text {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => text
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| anyhow!("unsupported value '{}'", text)),
+    }
+}
+
+/// Splits the first array item off `text`, respecting quotes.
+fn split_item(text: &str) -> Result<(&str, &str)> {
+    let end = match text.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let mut escaped = false;
+            text.char_indices()
+                .skip(1)
+                .find(|&(_, c)| {
+                    let close = c == quote && !escaped;
+                    escaped = quote == '"' && c == '\\' && !escaped;
+                    close
+                })
+                .map(|(i, _)| i + 1)
+                .ok_or_else(|| anyhow!("unterminated string in {}", text))?
+        }
+        _ => text.find(',').unwrap_or(text.len()),
+    };
+    Ok(text.split_at(end))
+}
+
+/// Drops a trailing comment, leaving `#` inside strings alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), _) if q == c && !escaped => quote = None,
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Parses the `key = value` lines of a flat TOML file. Arrays may span lines.
+fn parse_toml(text: &str) -> Result<HashMap<String, Value>> {
+    let mut values = HashMap::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected `key = value`", number + 1))?;
+        let mut value = value.trim().to_string();
+        while value.starts_with('[') && !value.ends_with(']') {
+            let (_, next) = lines
+                .next()
+                .ok_or_else(|| anyhow!("line {}: unterminated array", number + 1))?;
+            value.push_str(strip_comment(next).trim());
+        }
+        let value = parse_value(&value).map_err(|e| anyhow!("line {}: {}", number + 1, e))?;
+        values.insert(key.trim().trim_matches('"').to_string(), value);
+    }
+    Ok(values)
+}
+
+/// Settings read from a `.conan-doxygen.toml` file.
+struct Config {
+    values: HashMap<String, Value>,
+    /// Relative paths in the file are relative to the file itself.
+    dir: PathBuf,
+}
+
+impl Config {
+    fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config '{}': {}", path.display(), e))?;
+        let values = parse_toml(&text)
+            .map_err(|e| anyhow!("Failed to parse config '{}': {}", path.display(), e))?;
+        if let Some(key) = values.keys().find(|key| !KEYS.contains(&key.as_str())) {
+            return Err(anyhow!(
+                "Unknown key '{}' in config '{}'",
+                key,
+                path.display()
+            ));
+        }
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(Config { values, dir })
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>> {
+        self.values
+            .get(key)
+            .map(|value| {
+                serde_json::from_value(value.clone())
+                    .map_err(|_| anyhow!("'{}' in config should be a string", key))
+            })
+            .transpose()
+    }
+
+    fn flag(&self, key: &str) -> Result<Option<bool>> {
+        self.values
+            .get(key)
+            .map(|value| {
+                value
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("'{}' in config should be true or false", key))
+            })
+            .transpose()
+    }
+
+    /// A list of strings, where a single string counts as a list of one.
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>> {
+        self.values
+            .get(key)
+            .map(|value| match value {
+                Value::String(value) => Ok(vec![value.clone()]),
+                _ => serde_json::from_value(value.clone())
+                    .map_err(|_| anyhow!("'{}' in config should be a list of strings", key)),
+            })
+            .transpose()
+    }
+
+    fn path(&self, key: &str) -> Result<Option<PathBuf>> {
+        Ok(self.string(key)?.map(|path| self.dir.join(path)))
+    }
+}
+
+impl GenerateOptions {
+    /// `--config`, or the `.conan-doxygen.toml` next to the package if there
+    /// is one.
+    fn config_path(&self) -> Option<PathBuf> {
+        if let Some(path) = &self.config {
+            return Some(path.clone());
+        }
+        let path = self.src.join(CONFIG_FILE);
+        let src = self.src.to_str().unwrap_or_default();
+        (!is_reference(src) && path.is_file()).then_some(path)
+    }
+
+    /// Fills in options from `--config`, or from a `.conan-doxygen.toml` next
+    /// to the package, for every option that wasn't set in `matches`.
+    pub fn apply_config(&mut self, matches: &ArgMatches) -> Result<()> {
+        let Some(path) = self.config_path() else {
+            return Ok(());
+        };
+        let config = Config::load(&path)?;
+        if !self.quiet {
+            println!("Using settings from {}", path.display());
+        }
+
+        // `id` is the argument the key stands in for, flags win over the file
+        let from_config = |key: &str, id: &str| {
+            config.values.contains_key(key)
+                && matches.value_source(id) != Some(ValueSource::CommandLine)
+        };
+        if from_config("profile", "profile") {
+            self.profile = config.string("profile")?.unwrap_or_default();
+        }
+        if from_config("exclude", "exclude_patterns") {
+            self.exclude_patterns = config.strings("exclude")?.unwrap_or_default();
+        }
+        if from_config("file_patterns", "file_patterns") {
+            self.file_patterns = config.strings("file_patterns")?.unwrap_or_default();
+        }
+        if from_config("format", "format") {
+            self.format = config
+                .strings("format")?
+                .unwrap_or_default()
+                .iter()
+                .map(|format| {
+                    Format::from_str(format, true)
+                        .map_err(|_| anyhow!("Unknown format '{}' in config", format))
+                })
+                .collect::<Result<_>>()?;
+        }
+        if from_config("out", "out") {
+            self.out = config.path("out")?;
+        }
+        if from_config("template", "template") {
+            self.template = config.path("template")?;
+        }
+        if from_config("extract_all", "extract_all") && from_config("extract_all", "no_extract_all")
+        {
+            self.no_extract_all = !config.flag("extract_all")?.unwrap_or(true);
+        }
+        Ok(())
+    }
+
+    /// The settings a run would use once [`apply_config`](Self::apply_config)
+    /// has filled them in, each with where it came from: the command line,
+    /// the config file or the default. A list of `{"name", "value", "from"}`
+    /// objects, in a fixed order.
+    pub fn effective_config(&self, matches: &ArgMatches) -> Result<Value> {
+        let path = self.config_path();
+        let config = path.as_deref().map(Config::load).transpose()?;
+        let from = |key: Option<&str>, id: &str| match matches.value_source(id) {
+            Some(ValueSource::CommandLine) => "command line",
+            _ if key.is_some_and(|key| {
+                config
+                    .as_ref()
+                    .is_some_and(|config| config.values.contains_key(key))
+            }) =>
+            {
+                "config file"
+            }
+            Some(ValueSource::EnvVariable) => "environment",
+            _ => "default",
+        };
+        let formats: Vec<String> = self
+            .format
+            .iter()
+            .filter_map(|format| format.to_possible_value())
+            .map(|format| format.get_name().to_string())
+            .collect();
+        // Relative to the current directory, like the run resolves it
+        let out = match &self.out {
+            Some(out) => Value::from(env::current_dir()?.join(out).display().to_string()),
+            None => {
+                let package = PackageInfo::bare(String::from("<name>"), String::from("<version>"));
+                let src = self.src.to_str().unwrap_or_default();
+                Value::from(default_output(src, &package).display().to_string())
+            }
+        };
+        let template = match &self.template {
+            Some(template) => Value::from(template.display().to_string()),
+            None => Value::from("bundled"),
+        };
+        let binary = |program: &Path| match which(program) {
+            Some(path) => Value::from(path.display().to_string()),
+            None => Value::from(format!("{} (not found)", program.display())),
+        };
+        let doxygen = self
+            .doxygen_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("doxygen"));
+        let settings = [
+            (
+                "src",
+                Value::from(self.src.display().to_string()),
+                "command line",
+            ),
+            (
+                "config",
+                path.map_or(Value::Null, |path| Value::from(path.display().to_string())),
+                from(None, "config"),
+            ),
+            (
+                "profile",
+                Value::from(self.profile.clone()),
+                from(Some("profile"), "profile"),
+            ),
+            (
+                "format",
+                Value::from(formats),
+                from(Some("format"), "format"),
+            ),
+            (
+                "exclude",
+                Value::from(self.exclude_patterns.clone()),
+                from(Some("exclude"), "exclude_patterns"),
+            ),
+            (
+                "file_patterns",
+                Value::from(self.file_patterns.clone()),
+                from(Some("file_patterns"), "file_patterns"),
+            ),
+            ("template", template, from(Some("template"), "template")),
+            ("out", out, from(Some("out"), "out")),
+            (
+                "extract_all",
+                Value::from(!self.no_extract_all),
+                match matches.value_source("no_extract_all") {
+                    Some(ValueSource::CommandLine) => "command line",
+                    _ => from(Some("extract_all"), "extract_all"),
+                },
+            ),
+            ("doxygen", binary(&doxygen), from(None, "doxygen_path")),
+            (
+                "conan",
+                binary(Path::new(&self.conan_path)),
+                from(None, "conan_path"),
+            ),
+        ];
+        Ok(Value::from(
+            settings
+                .into_iter()
+                .map(|(name, value, from)| json!({ "name": name, "value": value, "from": from }))
+                .collect::<Vec<_>>(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_flat_toml_file() {
+        let text = r#"
+# Settings for the docs
+profile = "release#1"  # a comment
+"out" = 'build/api docs'
+extract_all = false
+exclude = [
+    "*/test/*", # tests
+    '*/bench/*',
+]
+file_patterns = ["*.h", "quote\"d", "comma, inside"]
+"#;
+
+        let values = parse_toml(text).unwrap();
+
+        assert_eq!(values.len(), 5);
+        assert_eq!(values["profile"], json!("release#1"));
+        assert_eq!(values["out"], json!("build/api docs"));
+        assert_eq!(values["extract_all"], json!(false));
+        assert_eq!(values["exclude"], json!(["*/test/*", "*/bench/*"]));
+        assert_eq!(
+            values["file_patterns"],
+            json!(["*.h", "quote\"d", "comma, inside"])
+        );
+    }
+
+    #[test]
+    fn keeps_an_escaped_quote_inside_a_string() {
+        let values = parse_toml(r##"profile = "a\"#b" # comment"##).unwrap();
+        assert_eq!(values["profile"], json!("a\"#b"));
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_value() {
+        let error = parse_toml("profile = \"default\"\nformat = html\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: unsupported value 'html'");
+
+        let error = parse_toml("exclude = [\"a\",\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: unterminated array");
+    }
+}
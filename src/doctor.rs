@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+
+use crate::{
+    conan::{conan_major_version, conan_version},
+    doxygen::{doxygen_version, predates, NEWER_SETTINGS},
+    process::CommandRunner,
+};
+
+fn pass(msg: String) {
+    println!("{} {}", "[pass]".green(), msg);
+}
+
+fn fail(msg: String) {
+    println!("{} {}", "[fail]".red(), msg);
+}
+
+fn warn(msg: String) {
+    println!("{} {}", "[warn]".yellow(), msg);
+}
+
+/// The first line a tool prints for its version flag. dot prints it to stderr.
+fn tool_version(runner: &dyn CommandRunner, program: &Path, flag: &str) -> Option<String> {
+    let output = runner.run(program.to_str()?, &[flag]).ok()?;
+    let text = [output.stdout, output.stderr].concat();
+    String::from_utf8_lossy(&text)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
+
+/// Checks for the tools the pipeline needs and prints a pass/fail line for
+/// each. Returns an error if a required tool is missing.
+pub fn doctor(runner: &dyn CommandRunner, conan: &str, doxygen: &Path) -> Result<()> {
+    let mut healthy = true;
+
+    match runner.resolve(Path::new(conan)) {
+        Some(path) => match conan_version(runner, conan) {
+            Ok(version) => pass(format!("conan {} ({})", version, path.display())),
+            Err(e) => {
+                healthy = false;
+                fail(format!(
+                    "conan at {} could not be run: {}",
+                    path.display(),
+                    e
+                ));
+            }
+        },
+        None => {
+            healthy = false;
+            fail(format!(
+                "conan not found at '{}'. Install it with `pip install conan` or set --conan-path.",
+                conan
+            ));
+        }
+    }
+
+    if let Ok(major) = conan_major_version(runner, conan) {
+        let code_path = if major >= 2 {
+            "`conan graph info`"
+        } else {
+            "`conan info`"
+        };
+        pass(format!(
+            "Conan {}.x detected, sources are gathered with {}",
+            major, code_path
+        ));
+    }
+
+    match runner.resolve(doxygen) {
+        Some(path) => {
+            let version = path.to_str().and_then(|path| doxygen_version(runner, path));
+            pass(format!(
+                "doxygen {} ({})",
+                version.as_deref().unwrap_or("of unknown version"),
+                path.display()
+            ));
+            for setting in NEWER_SETTINGS
+                .iter()
+                .filter(|setting| version.as_ref().is_some_and(|v| predates(v, setting.since)))
+            {
+                warn(format!(
+                    "doxygen predates {} (new in {}) and will ignore it for {}",
+                    setting.name, setting.since, setting.flag
+                ));
+            }
+        }
+        None => {
+            healthy = false;
+            fail(format!(
+                "doxygen not found at '{}'. Download it from https://github.com/doxygen/doxygen/releases or set --doxygen-path.",
+                doxygen.display()
+            ));
+        }
+    }
+
+    match runner.resolve(Path::new("dot")) {
+        Some(path) => pass(format!(
+            "{} ({})",
+            tool_version(runner, &path, "-V").unwrap_or_else(|| String::from("dot")),
+            path.display()
+        )),
+        None => warn(String::from(
+            "graphviz dot not found (optional), diagrams will not be generated",
+        )),
+    }
+
+    if healthy {
+        Ok(())
+    } else {
+        Err(anyhow!("Some required tools are missing"))
+    }
+}
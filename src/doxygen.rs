@@ -0,0 +1,993 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use handlebars::Handlebars;
+use serde_json::json;
+
+use crate::{
+    conan::{PackageInfo, Requirement},
+    error::Error,
+    process::{print_command, CommandRunner},
+    GenerateOptions,
+};
+
+/// A Doxyfile setting the bundled template only sets when asked to, which
+/// doxygen releases before `since` don't know and skip.
+pub(crate) struct NewerSetting {
+    pub(crate) name: &'static str,
+    pub(crate) flag: &'static str,
+    pub(crate) since: &'static str,
+    pub(crate) requested: fn(&GenerateOptions) -> bool,
+}
+
+pub(crate) const NEWER_SETTINGS: [NewerSetting; 2] = [
+    NewerSetting {
+        name: "FULL_SIDEBAR",
+        flag: "--full-sidebar",
+        since: "1.9.2",
+        requested: |options| options.full_sidebar,
+    },
+    NewerSetting {
+        name: "HTML_COLORSTYLE",
+        flag: "--theme awesome",
+        since: "1.9.5",
+        requested: |options| options.theme == Theme::Awesome,
+    },
+];
+
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.split('.').map(|part| part.parse().ok());
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().flatten().unwrap_or_default(),
+    ))
+}
+
+/// Whether doxygen `version` is older than `since`. Versions that can't be
+/// read count as new enough.
+pub(crate) fn predates(version: &str, since: &str) -> bool {
+    match (parse_version(version), parse_version(since)) {
+        (Some(version), Some(since)) => version < since,
+        _ => false,
+    }
+}
+
+/// Doxygen's version, e.g. "1.9.8" when it prints "1.9.8 (c2fe5c0...)".
+pub(crate) fn doxygen_version(runner: &dyn CommandRunner, doxygen: &str) -> Option<String> {
+    let output = runner.run(doxygen, &["--version"]).ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let version = stdout.split_whitespace().next()?;
+    parse_version(version)?;
+    Some(version.to_string())
+}
+
+pub(crate) const DOXYFILE_TEMPLATE: &str = include_str!("../template/DoxyFile.hbs");
+const LAYOUT: &str = include_str!("../template/Layout.xml");
+const STYLESHEETS: [(&str, &str); 2] = [
+    (
+        "doxygen-awesome.css",
+        include_str!("../template/doxygen-awesome.css"),
+    ),
+    (
+        "doxygen-awesome-sidebar-only.css",
+        include_str!("../template/doxygen-awesome-sidebar-only.css"),
+    ),
+];
+
+/// The values doxygen accepts for OUTPUT_LANGUAGE.
+const LANGUAGES: [&str; 45] = [
+    "Afrikaans",
+    "Arabic",
+    "Armenian",
+    "Brazilian",
+    "Bulgarian",
+    "Catalan",
+    "Chinese",
+    "Chinese-Traditional",
+    "Croatian",
+    "Czech",
+    "Danish",
+    "Dutch",
+    "English",
+    "Esperanto",
+    "Farsi",
+    "Finnish",
+    "French",
+    "German",
+    "Greek",
+    "Hindi",
+    "Hungarian",
+    "Indonesian",
+    "Italian",
+    "Japanese",
+    "Japanese-en",
+    "Korean",
+    "Korean-en",
+    "Latvian",
+    "Lithuanian",
+    "Macedonian",
+    "Norwegian",
+    "Persian",
+    "Polish",
+    "Portuguese",
+    "Romanian",
+    "Russian",
+    "Serbian",
+    "Serbian-Cyrillic",
+    "Slovak",
+    "Slovene",
+    "Spanish",
+    "Swedish",
+    "Turkish",
+    "Ukrainian",
+    "Vietnamese",
+];
+
+/// Parses `--language`, accepting any case and returning doxygen's spelling.
+pub(crate) fn language(value: &str) -> Result<String, String> {
+    LANGUAGES
+        .iter()
+        .find(|language| language.eq_ignore_ascii_case(value.trim()))
+        .map(|language| language.to_string())
+        .ok_or_else(|| {
+            format!(
+                "doxygen doesn't know the language, expected one of {}",
+                LANGUAGES.join(", ")
+            )
+        })
+}
+
+/// The look of the html output.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Theme {
+    /// doxygen-awesome-css with a tree view sidebar
+    Awesome,
+    /// Doxygen's stock stylesheet and navigation
+    Default,
+}
+
+/// What kind of sources doxygen arranges the docs for.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Optimize {
+    /// Data structures and functions rather than classes
+    C,
+    Cpp,
+    /// Packages and Java style naming
+    Java,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Html,
+    Latex,
+}
+
+/// Doxygen settings that come from the command line rather than from conan.
+pub(crate) struct DoxyfileOptions<'a> {
+    pub(crate) formats: &'a [Format],
+    pub(crate) exclude_patterns: &'a [String],
+    pub(crate) exclude_symbols: &'a [String],
+    pub(crate) file_patterns: &'a [String],
+    /// `NAME=VALUE` macros for PREDEFINED.
+    pub(crate) defines: &'a [String],
+    pub(crate) include_paths: &'a [String],
+    pub(crate) example_paths: &'a [String],
+    pub(crate) image_paths: &'a [String],
+    /// `name=value` pairs for ALIASES.
+    pub(crate) aliases: &'a [String],
+    pub(crate) input_filter: Option<&'a str>,
+    /// `PATTERN=CMD` pairs for FILTER_PATTERNS.
+    pub(crate) filter_patterns: &'a [String],
+    pub(crate) warnings_log: &'a Path,
+    pub(crate) dot: Option<&'a Path>,
+    pub(crate) doxy_folder: &'a Path,
+    pub(crate) tagfile: Option<&'a Path>,
+    /// `FILE=HTMLDIR` pairs of tag files to link against.
+    pub(crate) tagfiles: &'a [String],
+    pub(crate) mainpage: Option<&'a Path>,
+    /// Prefixes doxygen cuts off the file paths it shows.
+    pub(crate) strip_from_path: &'a [String],
+    pub(crate) logo: Option<&'a Path>,
+    pub(crate) language: &'a str,
+    /// PROJECT_NUMBER, the conan version unless overridden.
+    pub(crate) project_number: &'a str,
+    pub(crate) theme: Theme,
+    pub(crate) optimize_for: Optimize,
+    pub(crate) generate_treeview: bool,
+    pub(crate) full_sidebar: bool,
+    pub(crate) extract_all: bool,
+    pub(crate) extract_private: bool,
+    pub(crate) extract_static: bool,
+    pub(crate) internal_docs: bool,
+    pub(crate) source_browser: bool,
+    pub(crate) inline_sources: bool,
+    pub(crate) call_graph: bool,
+    pub(crate) caller_graph: bool,
+    /// Add a page showing what the package depends on.
+    pub(crate) depgraph: bool,
+    pub(crate) template: &'a str,
+    pub(crate) dry_run: bool,
+}
+
+/// Renders values as a doxygen list, quoting each value so paths with spaces
+/// survive and continuing onto a new line per value.
+fn doxy_list(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|v| format!("\"{}\"", v))
+        .collect::<Vec<String>>()
+        .join(" \\\n                      ")
+}
+
+/// A description squeezed onto one line that can sit in a quoted doxygen
+/// value, for PROJECT_BRIEF.
+fn brief(description: &str) -> String {
+    description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('"', "'")
+}
+
+/// The package name as it can sit in the quoted PROJECT_NAME.
+fn project_name(name: &str) -> String {
+    name.replace('"', "'")
+}
+
+fn yes_no(value: bool) -> String {
+    String::from(if value { "YES" } else { "NO" })
+}
+
+/// Checks that `logo` is an image doxygen can put in the page header.
+pub(crate) fn check_logo(logo: &Path) -> Result<()> {
+    const EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "svg"];
+    if !logo.is_file() {
+        return Err(anyhow!("Logo '{}' does not exist", logo.display()));
+    }
+    let extension = logo
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    if !extension.is_some_and(|ext| EXTENSIONS.contains(&ext.as_str())) {
+        return Err(anyhow!(
+            "Logo '{}' is not an image doxygen can use, expected one of {}",
+            logo.display(),
+            EXTENSIONS.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// The deepest folder containing all of `folders`, so the docs can show paths
+/// relative to it rather than into the conan cache. `None` when that is only
+/// the root.
+pub(crate) fn common_prefix(folders: &[String]) -> Option<PathBuf> {
+    let mut folders = folders
+        .iter()
+        .filter_map(|folder| Path::new(folder).canonicalize().ok());
+    let mut prefix = folders.next()?;
+    for folder in folders {
+        while !folder.starts_with(&prefix) {
+            if !prefix.pop() {
+                return None;
+            }
+        }
+    }
+    prefix.parent().is_some().then_some(prefix)
+}
+
+/// The subfolders called `name` of `folders` that exist, e.g. the `include`
+/// folders packages keep their headers in.
+pub(crate) fn subfolders(folders: &[String], name: &str) -> Vec<String> {
+    folders
+        .iter()
+        .map(|folder| Path::new(folder).join(name))
+        .filter(|subfolder| subfolder.is_dir())
+        .map(|subfolder| subfolder.display().to_string())
+        .collect()
+}
+
+/// Looks for a README to use as the main page in `folders`, in order.
+pub(crate) fn find_mainpage(folders: &[PathBuf]) -> Option<PathBuf> {
+    const NAMES: [&str; 4] = ["README.md", "readme.md", "Readme.md", "README.markdown"];
+    folders
+        .iter()
+        .flat_map(|folder| NAMES.map(|name| folder.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Tag files of dependencies documented into `dir`, e.g. by running this tool
+/// with `--out <DIR>/fmt --tagfile <DIR>/fmt/fmt.tag`.
+pub(crate) fn discover_tagfiles(dir: &Path, requires: &[Requirement]) -> Vec<String> {
+    requires
+        .iter()
+        .filter_map(|requirement| {
+            let name = &requirement.name;
+            let docs = dir.join(name);
+            let tagfile = docs.join(format!("{}.tag", name));
+            tagfile
+                .is_file()
+                .then(|| format!("{}={}", tagfile.display(), docs.join("html").display()))
+        })
+        .collect()
+}
+
+/// A doxygen page listing the package's dependencies, with a diagram of them
+/// when dot is available to draw it. Build and python requirements get lists
+/// of their own, and dashed and dotted arrows.
+fn depgraph_page(package: &PackageInfo, have_dot: bool) -> String {
+    let kinds = [
+        ("depends on", &package.requires, "solid"),
+        ("is built with", &package.build_requires, "dashed"),
+        ("reuses the recipes of", &package.python_requires, "dotted"),
+    ];
+    let mut page = String::from("/**\n\\page dependencies Dependencies\n");
+    for (relation, requirements, _) in kinds.iter().filter(|(_, r, _)| !r.is_empty()) {
+        page.push_str(&format!(
+            "\n{}/{} {}:\n\n",
+            package.name, package.version, relation
+        ));
+        for requirement in requirements.iter() {
+            page.push_str(&format!("- {}\n", requirement));
+        }
+    }
+    if have_dot {
+        page.push_str("\n\\dot\ndigraph dependencies {\n");
+        page.push_str("    node [shape=box, fontname=Helvetica, fontsize=10];\n");
+        for (_, requirements, style) in kinds {
+            for requirement in requirements {
+                page.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [style={}];\n",
+                    package.name, requirement.name, style
+                ));
+            }
+        }
+        page.push_str("}\n\\enddot\n");
+    }
+    page.push_str("*/\n");
+    page
+}
+
+/// Checks a rendered Doxyfile sets the keys without which doxygen documents
+/// the wrong files or writes somewhere unexpected, e.g. because a custom
+/// template lost a placeholder. INPUT is only checked when there were
+/// `inputs` to render into it.
+fn check_doxyfile(doxyfile: &str, inputs: &[String]) -> Result<()> {
+    const REQUIRED: [&str; 3] = ["PROJECT_NAME", "INPUT", "OUTPUT_DIRECTORY"];
+    let is_set = |key: &str| {
+        doxyfile.lines().any(|line| {
+            line.trim_start()
+                .strip_prefix(key)
+                .and_then(|rest| rest.trim_start().strip_prefix('='))
+                .is_some_and(|value| !value.trim().trim_matches('"').is_empty())
+        })
+    };
+    let missing: Vec<&str> = REQUIRED
+        .into_iter()
+        .filter(|key| !is_set(key) && (*key != "INPUT" || !inputs.is_empty()))
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "The Doxyfile template doesn't set {}, check that its placeholders are intact",
+            missing.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn generate_doxyfile(
+    package: &PackageInfo,
+    sources: &[String],
+    output: &Path,
+    options: &DoxyfileOptions,
+) -> Result<(String, PathBuf)> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let mut handlebar_data = HashMap::new();
+    handlebar_data.insert("name", json!(project_name(&package.name)));
+    handlebar_data.insert("version", json!(package.version));
+    handlebar_data.insert("project_number", json!(options.project_number));
+    let requires: Vec<String> = package.requires.iter().map(|r| r.to_string()).collect();
+    handlebar_data.insert("requires", json!(requires));
+    let build_requires: Vec<String> = package
+        .build_requires
+        .iter()
+        .map(|r| r.to_string())
+        .collect();
+    handlebar_data.insert("build_requires", json!(build_requires));
+    let python_requires: Vec<String> = package
+        .python_requires
+        .iter()
+        .map(|r| r.to_string())
+        .collect();
+    handlebar_data.insert("python_requires", json!(python_requires));
+    handlebar_data.insert("license", json!(package.license));
+    handlebar_data.insert("description", json!(package.description));
+    handlebar_data.insert("brief", json!(brief(&package.description)));
+    handlebar_data.insert("homepage", json!(package.homepage));
+    handlebar_data.insert("url", json!(package.url));
+    handlebar_data.insert("topics", json!(package.topics));
+    // The main page has to be among the inputs, which a README next to the
+    // conanfile usually isn't
+    let mut inputs = sources.to_vec();
+    if let Some(mainpage) = options.mainpage {
+        let listed = sources.iter().any(|source| {
+            Path::new(source)
+                .canonicalize()
+                .is_ok_and(|source| mainpage.starts_with(source))
+        });
+        if !listed {
+            inputs.push(mainpage.display().to_string());
+        }
+    }
+    let has_requirements = [
+        &package.requires,
+        &package.build_requires,
+        &package.python_requires,
+    ]
+    .iter()
+    .any(|requirements| !requirements.is_empty());
+    // Dry runs don't look for sources, so they may have none to list
+    if inputs.is_empty() && !options.dry_run {
+        return Err(anyhow!(
+            "No sources found to document, doxygen would have no INPUT"
+        ));
+    }
+    if options.depgraph && has_requirements {
+        let page = options.doxy_folder.join("dependencies.dox");
+        fs::create_dir_all(options.doxy_folder)?;
+        fs::write(&page, depgraph_page(package, options.dot.is_some()))?;
+        inputs.push(page.display().to_string());
+    }
+    handlebar_data.insert("sources", json!(doxy_list(&inputs)));
+    handlebar_data.insert("strip_from_path", json!(doxy_list(options.strip_from_path)));
+    handlebar_data.insert("mainpage", json!(options.mainpage.unwrap_or(Path::new(""))));
+    handlebar_data.insert("output", json!(output));
+    handlebar_data.insert("language", json!(options.language));
+    handlebar_data.insert(
+        "generate_html",
+        json!(yes_no(options.formats.contains(&Format::Html))),
+    );
+    handlebar_data.insert(
+        "generate_latex",
+        json!(yes_no(options.formats.contains(&Format::Latex))),
+    );
+    handlebar_data.insert(
+        "exclude_patterns",
+        json!(doxy_list(options.exclude_patterns)),
+    );
+    handlebar_data.insert("exclude_symbols", json!(doxy_list(options.exclude_symbols)));
+    handlebar_data.insert("file_patterns", json!(doxy_list(options.file_patterns)));
+    handlebar_data.insert(
+        "macro_expansion",
+        json!(yes_no(!options.defines.is_empty())),
+    );
+    handlebar_data.insert("predefined", json!(doxy_list(options.defines)));
+    handlebar_data.insert("include_path", json!(doxy_list(options.include_paths)));
+    // Alias values are often quoted text themselves
+    let aliases: Vec<String> = options
+        .aliases
+        .iter()
+        .map(|alias| alias.replace('"', "\\\""))
+        .collect();
+    handlebar_data.insert("aliases", json!(doxy_list(&aliases)));
+    // C++ is doxygen's default, so it needs neither
+    handlebar_data.insert(
+        "optimize_output_for_c",
+        json!(yes_no(options.optimize_for == Optimize::C)),
+    );
+    handlebar_data.insert(
+        "optimize_output_java",
+        json!(yes_no(options.optimize_for == Optimize::Java)),
+    );
+    handlebar_data.insert("example_path", json!(doxy_list(options.example_paths)));
+    handlebar_data.insert("image_path", json!(doxy_list(options.image_paths)));
+    handlebar_data.insert(
+        "input_filter",
+        json!(options.input_filter.unwrap_or_default()),
+    );
+    handlebar_data.insert("filter_patterns", json!(doxy_list(options.filter_patterns)));
+    handlebar_data.insert("warnings_log", json!(options.warnings_log));
+    handlebar_data.insert("tagfile", json!(options.tagfile.unwrap_or(Path::new(""))));
+    handlebar_data.insert("tagfiles", json!(doxy_list(options.tagfiles)));
+    handlebar_data.insert("extract_all", json!(yes_no(options.extract_all)));
+    handlebar_data.insert("extract_private", json!(yes_no(options.extract_private)));
+    handlebar_data.insert("extract_static", json!(yes_no(options.extract_static)));
+    handlebar_data.insert("internal_docs", json!(yes_no(options.internal_docs)));
+    handlebar_data.insert("source_browser", json!(yes_no(options.source_browser)));
+    handlebar_data.insert("inline_sources", json!(yes_no(options.inline_sources)));
+    // Browsed sources read better with their comments left in
+    handlebar_data.insert(
+        "strip_code_comments",
+        json!(yes_no(!options.source_browser)),
+    );
+    handlebar_data.insert("call_graph", json!(yes_no(options.call_graph)));
+    handlebar_data.insert("caller_graph", json!(yes_no(options.caller_graph)));
+    handlebar_data.insert("have_dot", json!(yes_no(options.dot.is_some())));
+    handlebar_data.insert(
+        "dot_path",
+        json!(options.dot.and_then(Path::parent).unwrap_or(Path::new(""))),
+    );
+
+    let doxy_file_out = options.doxy_folder.join("DoxyFile");
+
+    fs::create_dir_all(options.doxy_folder).with_context(|| {
+        format!(
+            "Failed to create Doxyfile folder '{}'",
+            options.doxy_folder.display()
+        )
+    })?;
+
+    // The layout and stylesheets are embedded, so doxygen gets its own copies
+    let layout_out = options.doxy_folder.join("Layout.xml");
+    fs::write(&layout_out, LAYOUT)?;
+    let mut stylesheets = Vec::new();
+    let themed = options.theme == Theme::Awesome;
+    if themed {
+        for (file_name, contents) in STYLESHEETS {
+            let stylesheet_out = options.doxy_folder.join(file_name);
+            fs::write(&stylesheet_out, contents)?;
+            stylesheets.push(stylesheet_out.display().to_string());
+        }
+    }
+    handlebar_data.insert("layout", json!(layout_out));
+    handlebar_data.insert("awesome", json!(themed));
+    // The sidebar only exists as part of the tree view
+    handlebar_data.insert(
+        "generate_treeview",
+        json!(yes_no(
+            themed || options.generate_treeview || options.full_sidebar
+        )),
+    );
+    handlebar_data.insert("full_sidebar", json!(yes_no(options.full_sidebar)));
+    // Copied next to the Doxyfile like the stylesheets, doxygen copies it on
+    // into the html output
+    let logo_out = match (options.logo, options.logo.and_then(Path::file_name)) {
+        (Some(logo), Some(file_name)) => {
+            let logo_out = options.doxy_folder.join(file_name);
+            fs::copy(logo, &logo_out)?;
+            logo_out
+        }
+        _ => PathBuf::new(),
+    };
+    handlebar_data.insert("logo", json!(logo_out));
+    handlebar_data.insert("stylesheets", json!(doxy_list(&stylesheets)));
+
+    handlebars.register_template_string("doxyfile", options.template)?;
+    let doxyfile = handlebars.render("doxyfile", &handlebar_data)?;
+    check_doxyfile(&doxyfile, &inputs)?;
+    fs::write(&doxy_file_out, doxyfile)?;
+    Ok((String::from("Generated DoxyFile"), doxy_file_out))
+}
+
+/// Runs the makefile doxygen writes next to the LaTeX sources. Without a LaTeX
+/// toolchain the sources are still usable, so this is skipped rather than failed.
+pub(crate) fn build_pdf(
+    runner: &dyn CommandRunner,
+    output: &Path,
+    verbose: bool,
+) -> Result<(String, Option<PathBuf>)> {
+    let found = |program: &str| runner.resolve(Path::new(program)).is_some();
+    if !found("make") || !found("pdflatex") {
+        return Ok((
+            String::from("LaTeX toolchain not found, skipped building PDF"),
+            None,
+        ));
+    }
+
+    let latex_folder = output.join("latex");
+    let latex_folder_str = latex_folder
+        .to_str()
+        .ok_or_else(|| anyhow!("Failed to convert LaTeX folder to str"))?;
+    let args = ["-C", latex_folder_str];
+    let status = if verbose {
+        runner.stream("make", &args)?
+    } else {
+        runner.run("make", &args)?.status
+    };
+    if !status.success() {
+        return Err(anyhow!(
+            "Failed to build PDF in {} ({})",
+            latex_folder.display(),
+            status
+        ));
+    }
+    Ok((
+        String::from("Built PDF"),
+        Some(latex_folder.join("refman.pdf")),
+    ))
+}
+
+/// Counts the lines doxygen reports as `file:line: warning: ...`.
+fn count_warnings(warnings: &str) -> usize {
+    warnings
+        .lines()
+        .filter(|line| line.contains("warning:"))
+        .count()
+}
+
+/// Runs doxygen, returning its exit status and the number of warnings it
+/// produced, or `None` for dry runs.
+///
+/// Warnings are read from `warnings_log`, which the Doxyfile points
+/// WARN_LOGFILE at; templates that don't set it fall back to stderr.
+pub(crate) fn run_doxygen(
+    runner: &dyn CommandRunner,
+    doxygen: &str,
+    doxy_file_out: &Path,
+    warnings_log: &Path,
+    timeout: Option<Duration>,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<(String, Option<(ExitStatus, usize)>)> {
+    let doxy_file_out = doxy_file_out
+        .to_str()
+        .ok_or(anyhow!("outpath could not be resolved"))?;
+    let args = [doxy_file_out];
+    if dry_run {
+        print_command(doxygen, &args);
+        return Ok((String::from("Skipped doxygen (dry run)"), None));
+    }
+
+    // Don't mistake a log left over from an earlier run for this one's
+    let _ = fs::remove_file(warnings_log);
+    let (status, stderr) = if verbose {
+        runner
+            .stream_timeout(doxygen, &args, timeout)
+            .map(|status| (status, Vec::new()))
+    } else {
+        runner
+            .run_timeout(doxygen, &args, timeout)
+            .map(|output| (output.status, output.stderr))
+    }
+    .map_err(|e| match e.downcast::<Error>() {
+        // A timeout already names doxygen, and keeps its variant
+        Ok(e) => e.into(),
+        Err(e) => anyhow!("Failed to run doxygen at '{}': {}", doxygen, e),
+    })?;
+
+    let warnings = fs::read_to_string(warnings_log)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&stderr).to_string());
+    if verbose {
+        eprint!("{}", warnings);
+    }
+
+    let warning_count = count_warnings(&warnings);
+    Ok((
+        format!("Doxygen produced {} warnings", warning_count),
+        Some((status, warning_count)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    /// A scratch Doxyfile folder, and options rendering into it as the CLI
+    /// would by default.
+    struct Fixture {
+        scratch: ScratchDir,
+        warnings_log: PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let scratch = ScratchDir::new(false).unwrap();
+            let warnings_log = scratch.path().join("warnings.log");
+            Fixture {
+                scratch,
+                warnings_log,
+            }
+        }
+
+        fn options(&self) -> DoxyfileOptions<'_> {
+            DoxyfileOptions {
+                formats: &[Format::Html],
+                exclude_patterns: &[],
+                exclude_symbols: &[],
+                file_patterns: &[],
+                defines: &[],
+                include_paths: &[],
+                example_paths: &[],
+                image_paths: &[],
+                aliases: &[],
+                input_filter: None,
+                filter_patterns: &[],
+                warnings_log: &self.warnings_log,
+                dot: None,
+                doxy_folder: self.scratch.path(),
+                tagfile: None,
+                tagfiles: &[],
+                mainpage: None,
+                strip_from_path: &[],
+                logo: None,
+                language: "English",
+                project_number: "1.2.3",
+                theme: Theme::Awesome,
+                optimize_for: Optimize::Cpp,
+                generate_treeview: false,
+                full_sidebar: false,
+                extract_all: true,
+                extract_private: false,
+                extract_static: false,
+                internal_docs: false,
+                source_browser: false,
+                inline_sources: false,
+                call_graph: false,
+                caller_graph: false,
+                depgraph: true,
+                template: DOXYFILE_TEMPLATE,
+                dry_run: false,
+            }
+        }
+
+        /// The rendered Doxyfile, with the scratch folder written as `<doxy>`.
+        fn render(
+            &self,
+            package: &PackageInfo,
+            sources: &[&str],
+            options: &DoxyfileOptions,
+        ) -> String {
+            let sources: Vec<String> = sources.iter().map(|source| source.to_string()).collect();
+            let (_, doxyfile) =
+                generate_doxyfile(package, &sources, Path::new("docs/mypkg_1.2.3"), options)
+                    .unwrap();
+            let scratch = self.scratch.path().display().to_string();
+            fs::read_to_string(doxyfile)
+                .unwrap()
+                .replace(
+                    &format!("{}{}", scratch, std::path::MAIN_SEPARATOR),
+                    "<doxy>/",
+                )
+                .replace(&scratch, "<doxy>")
+        }
+    }
+
+    /// The values of `key` the way doxygen reads them: continued onto the
+    /// following lines by a trailing backslash, and split at whitespace
+    /// outside quotes.
+    fn doxygen_values(doxyfile: &str, key: &str) -> Vec<String> {
+        let mut lines = doxyfile
+            .lines()
+            .skip_while(|line| line.split('=').next().map(str::trim) != Some(key));
+        let mut text = lines
+            .next()
+            .and_then(|line| line.split_once('='))
+            .map(|(_, value)| value.to_string())
+            .unwrap_or_default();
+        while let Some(continued) = text.trim_end().strip_suffix('\\') {
+            text = format!("{} {}", continued, lines.next().unwrap_or_default());
+        }
+
+        let mut values = Vec::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => values.push(chars.by_ref().take_while(|&c| c != '"').collect()),
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut value = String::from(c);
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        value.push(c);
+                    }
+                    values.push(value);
+                }
+            }
+        }
+        values
+    }
+
+    fn package() -> PackageInfo {
+        PackageInfo {
+            requires: vec![Requirement {
+                name: String::from("fmt"),
+                version: String::from("9.1.0"),
+            }],
+            description: String::from("A package\nfor tests"),
+            ..PackageInfo::bare(String::from("mypkg"), String::from("1.2.3"))
+        }
+    }
+
+    #[test]
+    fn keeps_folders_with_spaces_as_single_inputs() {
+        let fixture = Fixture::new();
+        let sources = [
+            "Library/Application Support/conan/fmt",
+            "my pkg/sources",
+            "plain",
+        ];
+
+        let doxyfile = fixture.render(
+            &PackageInfo::bare(String::from("my pkg"), String::from("1.0")),
+            &sources,
+            &fixture.options(),
+        );
+
+        assert_eq!(doxygen_values(&doxyfile, "INPUT"), sources);
+        assert_eq!(doxygen_values(&doxyfile, "PROJECT_NAME"), ["my pkg"]);
+    }
+
+    #[test]
+    fn gives_templates_the_requirements() {
+        let fixture = Fixture::new();
+        let template = format!(
+            "{}\n# Depends on{{{{#each requires}}}} {{{{this}}}}{{{{/each}}}}\n",
+            DOXYFILE_TEMPLATE
+        );
+        let package = PackageInfo {
+            requires: vec![
+                Requirement::parse("fmt/9.1.0"),
+                Requirement::parse("zlib/[>=1.2 <2]@"),
+            ],
+            ..package()
+        };
+
+        let doxyfile = fixture.render(
+            &package,
+            &["pkg/sources"],
+            &DoxyfileOptions {
+                template: &template,
+                ..fixture.options()
+            },
+        );
+
+        assert!(doxyfile.contains("# Depends on fmt/9.1.0 zlib/[>=1.2 <2]\n"));
+    }
+
+    #[test]
+    fn lists_500_folders_one_per_line() {
+        let folders: Vec<String> = (0..500).map(|i| format!("deps/pkg{}/include", i)).collect();
+
+        let list = doxy_list(&folders);
+
+        let lines: Vec<&str> = list.lines().collect();
+        assert_eq!(lines.len(), 500);
+        for (i, (line, folder)) in lines.iter().zip(&folders).enumerate() {
+            let value = line.trim_start().trim_end_matches(" \\");
+            assert_eq!(value, format!("\"{}\"", folder));
+            // Every line but the last continues onto the next
+            assert_eq!(line.ends_with(" \\"), i < 499);
+        }
+    }
+
+    /// Compares the Doxyfile rendered from fixed inputs with the checked in
+    /// tests/golden/DoxyFile. Run with UPDATE_GOLDEN=1 to accept a change.
+    #[test]
+    fn renders_the_golden_doxyfile() {
+        let fixture = Fixture::new();
+        let rendered = fixture.render(&package(), &["pkg/sources", "deps/fmt"], &fixture.options());
+        let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join("DoxyFile");
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&golden, &rendered).unwrap();
+        }
+        let expected = fs::read_to_string(&golden).unwrap();
+        assert_eq!(
+            rendered, expected,
+            "the Doxyfile changed, run with UPDATE_GOLDEN=1 if that is intended"
+        );
+    }
+
+    #[test]
+    fn briefs_the_description_only_when_there_is_one() {
+        let fixture = Fixture::new();
+        let described = PackageInfo {
+            description: String::from("Formats \"things\"\n  quickly"),
+            ..package()
+        };
+        let undescribed = PackageInfo {
+            description: String::new(),
+            ..package()
+        };
+
+        let doxyfile = fixture.render(&described, &["pkg/sources"], &fixture.options());
+        assert_eq!(
+            doxygen_values(&doxyfile, "PROJECT_BRIEF"),
+            ["Formats 'things' quickly"]
+        );
+
+        let doxyfile = fixture.render(&undescribed, &["pkg/sources"], &fixture.options());
+        assert!(!doxyfile.contains("PROJECT_BRIEF"));
+    }
+
+    #[test]
+    fn keeps_a_quoted_name_in_one_value() {
+        let fixture = Fixture::new();
+        let quoted = PackageInfo {
+            name: String::from("my \"pkg\""),
+            ..package()
+        };
+
+        let doxyfile = fixture.render(&quoted, &["pkg/sources"], &fixture.options());
+        assert_eq!(doxygen_values(&doxyfile, "PROJECT_NAME"), ["my 'pkg'"]);
+    }
+
+    #[test]
+    fn maps_the_extraction_flags_to_doxygen_settings() {
+        let fixture = Fixture::new();
+        let keys = [
+            "EXTRACT_ALL",
+            "EXTRACT_PRIVATE",
+            "EXTRACT_STATIC",
+            "INTERNAL_DOCS",
+        ];
+        let values = |doxyfile: &str| {
+            keys.iter()
+                .map(|key| doxygen_values(doxyfile, key).concat())
+                .collect::<Vec<String>>()
+        };
+
+        let doxyfile = fixture.render(&package(), &["pkg/sources"], &fixture.options());
+        assert_eq!(values(&doxyfile), ["YES", "NO", "NO", "NO"]);
+
+        let options = DoxyfileOptions {
+            extract_all: false,
+            extract_private: true,
+            extract_static: true,
+            internal_docs: true,
+            ..fixture.options()
+        };
+        let doxyfile = fixture.render(&package(), &["pkg/sources"], &options);
+        assert_eq!(values(&doxyfile), ["NO", "YES", "YES", "YES"]);
+    }
+
+    #[test]
+    fn excludes_the_internal_namespaces_by_default() {
+        let fixture = Fixture::new();
+        let defaults = crate::GenerateOptions::new("pkg").exclude_symbols;
+        let options = DoxyfileOptions {
+            exclude_symbols: &defaults,
+            ..fixture.options()
+        };
+
+        let doxyfile = fixture.render(&package(), &["pkg/sources"], &options);
+        assert_eq!(
+            doxygen_values(&doxyfile, "EXCLUDE_SYMBOLS"),
+            ["detail", "*::detail", "impl", "*::impl"]
+        );
+
+        let doxyfile = fixture.render(&package(), &["pkg/sources"], &fixture.options());
+        assert!(doxygen_values(&doxyfile, "EXCLUDE_SYMBOLS").is_empty());
+    }
+
+    #[test]
+    fn renders_example_and_image_paths() {
+        let fixture = Fixture::new();
+        let with_examples = fixture.scratch.path().join("pkg");
+        let without_examples = fixture.scratch.path().join("fmt");
+        fs::create_dir_all(with_examples.join("examples")).unwrap();
+        fs::create_dir_all(&without_examples).unwrap();
+        let folders = [with_examples, without_examples].map(|folder| folder.display().to_string());
+
+        let example_paths: Vec<String> = std::iter::once(String::from("docs/snippets"))
+            .chain(subfolders(&folders, "examples"))
+            .collect();
+        let image_paths = [String::from("docs/images"), String::from("my images")];
+        let options = DoxyfileOptions {
+            example_paths: &example_paths,
+            image_paths: &image_paths,
+            ..fixture.options()
+        };
+        let doxyfile = fixture.render(&package(), &["pkg/sources"], &options);
+
+        assert_eq!(
+            doxygen_values(&doxyfile, "EXAMPLE_PATH"),
+            [
+                String::from("docs/snippets"),
+                format!("<doxy>/{}", Path::new("pkg").join("examples").display()),
+            ]
+        );
+        assert_eq!(doxygen_values(&doxyfile, "IMAGE_PATH"), image_paths);
+    }
+}
@@ -0,0 +1,75 @@
+use std::process::ExitStatus;
+
+use thiserror::Error;
+
+/// The ways a documentation run can fail that callers may want to tell apart.
+/// Anything else is reported as [`Error::Other`].
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(
+        "conan not found at '{path}'. Install it with `pip install conan` or set --conan-path."
+    )]
+    ConanNotFound { path: String },
+
+    /// Graphs were asked for with `flag` but dot isn't installed to draw them.
+    #[error("{flag} needs graphviz dot, which was not found in PATH. Install graphviz or leave out {flag}.")]
+    DotNotFound { flag: String },
+
+    #[error("doxygen not found at '{path}'. Download it from https://github.com/doxygen/doxygen/releases or set --doxygen-path.")]
+    DoxygenNotFound { path: String },
+
+    /// `conan install` exited unsuccessfully. `stderr` is the tail of its
+    /// output, empty when it was streamed.
+    #[error("conan install failed ({status}){}", on_new_line(stderr))]
+    ConanInstallFailed { status: ExitStatus, stderr: String },
+
+    #[error("Failed to parse conan output. Conan 1.x (>= 1.18) or 2.x (>= 2.0) is required")]
+    ConanInfoParse,
+
+    #[error("No documentable sources were found for '{src}'. Run `conan source` for it, or check that its sources are in a `sources` folder next to the conanfile.")]
+    NoSourcesFound { src: String },
+
+    /// Like [`Error::NoSourcesFound`], but conan install was skipped with
+    /// `--no-install`, which is the likelier cause.
+    #[error("No package folders were found for '{src}'. They may not be in the conan cache yet, run without --no-install to fetch them.")]
+    NotInstalled { src: String },
+
+    /// A program ran longer than it was given, by `--timeout` or by what was
+    /// left of `--deadline`, and was stopped.
+    #[error("'{program}' did not finish within {seconds} seconds and was stopped, see --timeout")]
+    TimedOut { program: String, seconds: u64 },
+
+    /// The run took longer than `--deadline`, and was stopped.
+    #[error("The run didn't finish within the --deadline of {seconds} seconds and was stopped")]
+    DeadlineExceeded { seconds: u64 },
+
+    #[error("Failed to generate docs, doxygen exited with {status} after {warnings} warnings")]
+    DoxygenFailed { status: ExitStatus, warnings: usize },
+
+    /// A `--pre-hook` or `--post-hook` script exited unsuccessfully. `stderr`
+    /// is the tail of its output, empty when it was streamed.
+    #[error("{hook} failed ({status}){}", on_new_line(stderr))]
+    HookFailed {
+        hook: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+fn on_new_line(details: &str) -> String {
+    if details.is_empty() {
+        String::new()
+    } else {
+        format!(":\n{}", details)
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    /// Recovers the variant of errors that were passed up as [`anyhow::Error`].
+    fn from(err: anyhow::Error) -> Self {
+        err.downcast::<Error>().unwrap_or_else(Error::Other)
+    }
+}
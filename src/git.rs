@@ -0,0 +1,31 @@
+use std::path::Path;
+
+use crate::process::{print_command, CommandRunner};
+
+/// `git describe --tags --dirty` for the checkout `folder` is in, e.g.
+/// `v1.2.0-3-gabc1234-dirty`. `None` when git isn't installed, the folder
+/// isn't a checkout or it has no tags, so callers can fall back to the conan
+/// version.
+pub(crate) fn describe(
+    runner: &dyn CommandRunner,
+    folder: &Path,
+    verbose: bool,
+    dry_run: bool,
+) -> Option<String> {
+    runner.resolve(Path::new("git"))?;
+    let folder = folder.to_str()?;
+    let args = ["-C", folder, "describe", "--tags", "--dirty"];
+    if verbose || dry_run {
+        print_command("git", &args);
+    }
+    if dry_run {
+        return None;
+    }
+
+    let output = runner.run("git", &args).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let description = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!description.is_empty()).then_some(description)
+}
@@ -0,0 +1,94 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    conan::PackageInfo,
+    process::{print_command, stderr_tail, CommandRunner},
+    Error,
+};
+
+/// Runs a `--pre-hook` or `--post-hook` script with the output folder as its
+/// last argument, and the package's name and version along with the output folder
+/// in `CONAN_DOXYGEN_NAME`, `CONAN_DOXYGEN_VERSION` and
+/// `CONAN_DOXYGEN_OUTPUT`. Returns what the script printed, which is empty
+/// when it was streamed.
+pub(crate) fn run_hook(
+    runner: &dyn CommandRunner,
+    hook: &str,
+    package: &PackageInfo,
+    output: &Path,
+    timeout: Option<Duration>,
+    verbose: bool,
+    dry_run: bool,
+) -> Result<(String, String)> {
+    let output = output
+        .to_str()
+        .ok_or_else(|| anyhow!("Failed to convert output path to str"))?;
+    // Like --open-with, the command may carry arguments of its own
+    let mut words = hook.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| anyhow!("The hook command must not be empty"))?;
+    let args: Vec<&str> = words.chain([output]).collect();
+    if verbose || dry_run {
+        print_command(program, &args);
+    }
+    if dry_run {
+        return Ok((format!("Skipped {} (dry run)", hook), String::new()));
+    }
+
+    let env = [
+        ("CONAN_DOXYGEN_NAME", package.name.as_str()),
+        ("CONAN_DOXYGEN_VERSION", package.version.as_str()),
+        ("CONAN_DOXYGEN_OUTPUT", output),
+    ]
+    .map(|(key, value)| (key.to_string(), value.to_string()));
+    let (status, printed, stderr) = if verbose {
+        let status = runner.stream_env(program, &args, &env, timeout)?;
+        (status, String::new(), String::new())
+    } else {
+        let output = runner.run_env(program, &args, &env, timeout)?;
+        (
+            output.status,
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr_tail(&output.stderr),
+        )
+    };
+    if !status.success() {
+        return Err(Error::HookFailed {
+            hook: hook.to_string(),
+            status,
+            stderr,
+        }
+        .into());
+    }
+    Ok((format!("Finished {}", hook), printed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeRunner;
+
+    #[test]
+    fn runs_a_hook_with_its_own_arguments_before_the_output() {
+        let runner = FakeRunner::new(|_, _| (0, String::from("deployed\n")));
+        let package = PackageInfo::bare(String::from("mypkg"), String::from("1.2.3"));
+
+        let (msg, printed) = run_hook(
+            &runner,
+            "rsync  -a --delete",
+            &package,
+            Path::new("/docs/mypkg"),
+            None,
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(runner.calls(), ["rsync -a --delete /docs/mypkg"]);
+        assert_eq!(msg, "Finished rsync  -a --delete");
+        assert_eq!(printed, "deployed\n");
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use handlebars::Handlebars;
+use serde_json::json;
+
+use crate::GenerateReport;
+
+const INDEX_TEMPLATE: &str = include_str!("../template/Index.html.hbs");
+
+/// Writes an `index.html` into `root` linking to the html docs of each of
+/// `reports`, as a single entry point for runs documenting several packages.
+/// Returns `None` when none of them produced html, e.g. for dry runs.
+pub fn write_index(root: &Path, reports: &[&GenerateReport]) -> Result<Option<PathBuf>> {
+    fs::create_dir_all(root)
+        .map_err(|e| anyhow!("Failed to create '{}': {}", root.display(), e))?;
+    let root = root.canonicalize()?;
+    let packages: Vec<_> = reports
+        .iter()
+        .filter_map(|report| {
+            let html_index = report
+                .docs
+                .iter()
+                .find(|doc| doc.ends_with("html/index.html"))?
+                .canonicalize()
+                .ok()?;
+            // Relative links keep working when the whole folder is moved
+            let href = html_index
+                .strip_prefix(&root)
+                .unwrap_or(&html_index)
+                .to_path_buf();
+            Some(json!({
+                "name": report.package.name,
+                "version": report.package.version,
+                "description": report.package.description,
+                "href": href,
+            }))
+        })
+        .collect();
+    if packages.is_empty() {
+        return Ok(None);
+    }
+
+    let handlebars = Handlebars::new();
+    let index = handlebars.render_template(INDEX_TEMPLATE, &json!({ "packages": packages }))?;
+    let index_out = root.join("index.html");
+    fs::write(&index_out, index)?;
+    Ok(Some(index_out))
+}
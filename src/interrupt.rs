@@ -0,0 +1,160 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Child,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use anyhow::Result;
+
+/// Process ids of the conan and doxygen runs in flight.
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+/// Scratch directories to remove if the run is interrupted.
+static SCRATCH_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether [`handle_interrupts`] was called. Children then get a process
+/// group of their own, which the handler stops as a whole.
+pub(crate) fn installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Keeps a child registered to be stopped on Ctrl-C until it is dropped.
+/// Dropping it while panicking stops the child as well.
+pub(crate) struct Running(u32);
+
+impl Running {
+    pub(crate) fn new(child: &Child) -> Self {
+        let pid = child.id();
+        CHILDREN.lock().unwrap_or_else(|e| e.into_inner()).push(pid);
+        Running(pid)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        CHILDREN
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|pid| *pid != self.0);
+        if thread::panicking() {
+            kill(self.0);
+        }
+    }
+}
+
+pub(crate) fn register_scratch(path: &Path) {
+    SCRATCH_DIRS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(path.to_path_buf());
+}
+
+pub(crate) fn unregister_scratch(path: &Path) {
+    SCRATCH_DIRS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|dir| dir != path);
+}
+
+/// Stops a child along with anything it started, when it runs in its own
+/// process group.
+pub(crate) fn kill(pid: u32) {
+    #[cfg(unix)]
+    {
+        let target = if installed() {
+            -(pid as i32)
+        } else {
+            pid as i32
+        };
+        unsafe {
+            libc::kill(target, libc::SIGTERM);
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .output();
+    }
+}
+
+#[cfg(unix)]
+fn clean_up() {
+    for pid in CHILDREN.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+        kill(pid);
+    }
+    for dir in SCRATCH_DIRS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(..)
+    {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::{
+        io,
+        sync::atomic::{AtomicI32, Ordering},
+    };
+
+    static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn on_signal(_: libc::c_int) {
+        // Only async-signal-safe calls here, the work happens on the thread
+        // reading the other end
+        let fd = PIPE.load(Ordering::Relaxed);
+        if fd >= 0 {
+            unsafe {
+                libc::write(fd, [1u8].as_ptr().cast(), 1);
+            }
+        }
+    }
+
+    /// Installs SIGINT and SIGTERM handlers, returning a callback that blocks
+    /// until one of them fires.
+    pub(super) fn install() -> io::Result<impl FnOnce() + Send> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        PIPE.store(fds[1], Ordering::Relaxed);
+        let handler = on_signal as extern "C" fn(libc::c_int);
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            unsafe {
+                libc::signal(signal, handler as libc::sighandler_t);
+            }
+        }
+        Ok(move || {
+            let mut byte = 0u8;
+            while unsafe { libc::read(fds[0], (&mut byte as *mut u8).cast(), 1) } < 0 {}
+        })
+    }
+}
+
+/// Stops running conan and doxygen processes and removes scratch directories
+/// when the process gets Ctrl-C or SIGTERM, then exits with status 130.
+/// Without it an interrupted run leaves them behind.
+pub fn handle_interrupts() -> Result<()> {
+    if INSTALLED.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+    // Elsewhere Ctrl-C already reaches the whole console, children included
+    #[cfg(unix)]
+    {
+        let wait = signals::install()?;
+        thread::spawn(move || {
+            wait();
+            clean_up();
+            eprintln!("\nInterrupted");
+            std::process::exit(130);
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,1754 @@
+//! Generate documentation for conan packages using doxygen.
+//!
+//! [`generate`] runs the whole pipeline: it inspects the package, installs its
+//! dependencies, collects their source folders and runs doxygen over them.
+
+use std::{
+    env,
+    ffi::OsString,
+    fmt, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    result::Result::Ok,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use clap::{Args, FromArgMatches, ValueEnum};
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::{json, Value};
+
+mod archive;
+mod completions;
+mod conan;
+mod config;
+mod doctor;
+mod doxygen;
+mod error;
+mod git;
+mod hook;
+mod index;
+mod interrupt;
+mod process;
+mod scratch;
+mod serve;
+#[cfg(test)]
+mod testing;
+mod watch;
+
+pub use completions::{completions, Shell};
+pub use conan::{PackageInfo, Requirement};
+pub use doctor::doctor;
+pub use doxygen::{Format, Optimize, Theme};
+pub use error::Error;
+pub use index::write_index;
+pub use interrupt::handle_interrupts;
+pub use process::{CommandRunner, SystemRunner};
+pub use serve::serve;
+pub use watch::watch;
+
+use archive::zip_folder;
+use conan::{conan_install, gather_sources, inspect, is_reference, InstallOptions};
+use doxygen::{
+    build_pdf, check_logo, common_prefix, discover_tagfiles, doxygen_version, find_mainpage,
+    generate_doxyfile, language, predates, run_doxygen, subfolders, DoxyfileOptions,
+    DOXYFILE_TEMPLATE, NEWER_SETTINGS,
+};
+use hook::run_hook;
+use process::WithEnv;
+use scratch::ScratchDir;
+
+/// Everything that controls a documentation run. The CLI flags are derived
+/// from these fields.
+#[derive(Debug, Clone, Args)]
+pub struct GenerateOptions {
+    #[arg(help = "Path to conan package, or a conan reference such as fmt/9.1.0@")]
+    pub src: PathBuf,
+
+    #[arg(
+        long,
+        help = "Path to output folder, relative to the current directory [default: <SRC>/build/docs/<name>_<version>]"
+    )]
+    pub out: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Settings file to read [default: <SRC>/.conan-doxygen.toml]"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(long, help = "Path to doxygen executable")]
+    pub doxygen_path: Option<PathBuf>,
+
+    #[arg(long, default_value = "conan", help = "Path to conan executable")]
+    pub conan_path: String,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Conan home to use instead of the current one, e.g. to give CI jobs a cache of their own"
+    )]
+    pub conan_home: Option<PathBuf>,
+
+    #[arg(
+        long = "env",
+        value_name = "KEY=VALUE",
+        value_parser = key_value,
+        help = "Environment variable to set for conan and doxygen, e.g. a proxy, may be repeated"
+    )]
+    pub env: Vec<String>,
+
+    #[arg(
+        long,
+        default_value = "default",
+        help = "Conan profile name or path to a profile file"
+    )]
+    pub profile: String,
+
+    #[arg(
+        long,
+        help = "Stream conan and doxygen output instead of showing progress"
+    )]
+    pub verbose: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "verbose",
+        help = "Only print where the docs are, and errors"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long,
+        help = "Print the conan and doxygen commands instead of running them"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        help = "How to show progress [default: spinner on a terminal, plain otherwise]"
+    )]
+    pub progress: Option<Progress>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "html",
+        help = "Output formats to generate, e.g. html,latex"
+    )]
+    pub format: Vec<Format>,
+
+    #[arg(
+        long,
+        help = "Path to a handlebars Doxyfile template to use instead of the bundled one"
+    )]
+    pub template: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        default_value = "English",
+        value_parser = language,
+        help = "Language of the text doxygen puts around the docs, e.g. Japanese or German"
+    )]
+    pub language: String,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Theme::Awesome,
+        help = "Look of the html output"
+    )]
+    pub theme: Theme,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Optimize::Cpp,
+        help = "Kind of sources to arrange the docs for, c for packages without classes"
+    )]
+    pub optimize_for: Optimize,
+
+    #[arg(
+        long,
+        help = "Add a tree view of the docs next to each page [default: with the awesome theme]"
+    )]
+    pub generate_treeview: bool,
+
+    #[arg(
+        long,
+        help = "Show the search box and main menu in the tree view sidebar rather than above the page"
+    )]
+    pub full_sidebar: bool,
+
+    #[arg(
+        long = "exclude",
+        value_name = "PATTERN",
+        default_values = ["*/test/*", "*/.conan/*"],
+        help = "Pattern of files or folders doxygen should skip, may be repeated"
+    )]
+    pub exclude_patterns: Vec<String>,
+
+    #[arg(
+        long = "exclude-symbols",
+        value_name = "PATTERN",
+        default_values = ["detail", "*::detail", "impl", "*::impl"],
+        help = "Namespace, class or function doxygen should leave out, e.g. ns::*Test, may be repeated"
+    )]
+    pub exclude_symbols: Vec<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "exclude_symbols",
+        help = "Document every symbol, including detail and impl namespaces"
+    )]
+    pub no_exclude_symbols: bool,
+
+    #[arg(
+        long = "file-patterns",
+        value_name = "GLOB",
+        help = "File pattern doxygen should scan, e.g. *.hxx, may be repeated [default: doxygen's]"
+    )]
+    pub file_patterns: Vec<String>,
+
+    #[arg(
+        long = "define",
+        value_name = "NAME=VALUE",
+        value_parser = key_value,
+        help = "Macro doxygen should expand while reading the sources, may be repeated"
+    )]
+    pub defines: Vec<String>,
+
+    #[arg(
+        long = "include-path",
+        value_name = "DIR",
+        help = "Folder doxygen should search for included headers besides the sources' include folders, may be repeated"
+    )]
+    pub include_paths: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "CMD",
+        value_parser = non_empty,
+        help = "Program doxygen should pipe every source file through, e.g. to strip attributes it can't parse"
+    )]
+    pub input_filter: Option<String>,
+
+    #[arg(
+        long = "example-path",
+        value_name = "DIR",
+        help = "Folder holding the files \\example and \\include refer to, may be repeated, on top of the examples folders of the sources"
+    )]
+    pub example_paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "alias",
+        value_name = "NAME=VALUE",
+        value_parser = key_value,
+        help = "Custom doxygen command, e.g. 'since=\\par Since:\\n', may be repeated"
+    )]
+    pub aliases: Vec<String>,
+
+    #[arg(
+        long = "image-path",
+        value_name = "DIR",
+        help = "Folder holding the images \\image refers to, may be repeated"
+    )]
+    pub image_paths: Vec<PathBuf>,
+
+    #[arg(
+        long = "filter-pattern",
+        value_name = "PATTERN=CMD",
+        value_parser = filter_pattern,
+        help = "Program to pipe the files matching a pattern through instead, e.g. *.hpp=./strip.sh, may be repeated"
+    )]
+    pub filter_patterns: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CMD",
+        help = "Wrapper to run conan install through, e.g. cdt"
+    )]
+    pub installer: Option<String>,
+
+    #[arg(
+        long,
+        help = "Run conan install even when the packages are already in the conan cache"
+    )]
+    pub force_install: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Save the output of conan install to this file, whether it succeeds or not"
+    )]
+    pub install_log: Option<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with = "force_install",
+        help = "Don't run conan install, for packages that are already in the conan cache"
+    )]
+    pub no_install: bool,
+
+    #[arg(
+        long = "build",
+        value_name = "SPEC",
+        value_parser = non_empty,
+        help = "Forwarded to conan install as --build=<SPEC>, e.g. missing, may be repeated"
+    )]
+    pub build_specs: Vec<String>,
+
+    #[arg(
+        long = "setting",
+        value_name = "KEY=VALUE",
+        value_parser = key_value,
+        help = "Conan setting, e.g. compiler=gcc, may be repeated"
+    )]
+    pub settings: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TYPE",
+        value_parser = non_empty,
+        help = "Conan build_type setting, e.g. Debug or Release, short for --setting build_type=<TYPE> [default: the profile's]"
+    )]
+    pub build_type: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Conan lockfile to install and gather sources from, pinning the dependency graph"
+    )]
+    pub lockfile: Option<PathBuf>,
+
+    #[arg(
+        long = "option",
+        value_name = "KEY=VALUE",
+        value_parser = key_value,
+        help = "Conan option, e.g. shared=True, may be repeated"
+    )]
+    pub options: Vec<String>,
+
+    #[arg(long, help = "Path to save doxygen's warnings to")]
+    pub warnings_log: Option<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with = "disable_dot",
+        help = "Require graphviz dot for diagrams, warning if it can't be found"
+    )]
+    pub enable_dot: bool,
+
+    #[arg(long, help = "Don't use graphviz dot even if it is installed")]
+    pub disable_dot: bool,
+
+    #[arg(
+        long,
+        overrides_with = "no_extract_all",
+        help = "Document every entity, even those without doc comments [default]"
+    )]
+    pub extract_all: bool,
+
+    #[arg(
+        long,
+        overrides_with = "extract_all",
+        help = "Only document entities that have doc comments"
+    )]
+    pub no_extract_all: bool,
+
+    #[arg(long, help = "Include private class members in the docs")]
+    pub extract_private: bool,
+
+    #[arg(
+        long,
+        help = "Include static members and file-local functions in the docs"
+    )]
+    pub extract_static: bool,
+
+    #[arg(long, help = "Include the docs marked \\internal")]
+    pub internal_docs: bool,
+
+    #[arg(
+        long,
+        help = "Add browsable copies of the source files, linked from the docs; makes the output much larger"
+    )]
+    pub source_browser: bool,
+
+    #[arg(long, help = "Show the body of each function in its docs")]
+    pub inline_sources: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "disable_dot",
+        help = "Draw a graph of the functions each function calls, needs graphviz dot"
+    )]
+    pub call_graph: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "disable_dot",
+        help = "Draw a graph of the functions calling each function, needs graphviz dot"
+    )]
+    pub caller_graph: bool,
+
+    #[arg(
+        long,
+        help = "Keep the scratch directory holding the conan install folder and Doxyfile"
+    )]
+    pub keep: bool,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Stop conan install or doxygen if either takes longer than this"
+    )]
+    pub timeout: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Stop the whole run, along with conan or doxygen, if it takes longer than this"
+    )]
+    pub deadline: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Stop once the Doxyfile is generated and keep it, to run doxygen yourself"
+    )]
+    pub doxyfile_only: bool,
+
+    #[arg(
+        long,
+        value_name = "CMD",
+        help = "Script to run before doxygen, with any arguments of its own, given the output folder, with the package name and version in CONAN_DOXYGEN_NAME and CONAN_DOXYGEN_VERSION"
+    )]
+    pub pre_hook: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "CMD",
+        help = "Script to run once the docs are generated, e.g. to deploy them, given the same as --pre-hook"
+    )]
+    pub post_hook: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        help = "Pack the html docs into a zip archive, e.g. for uploading [default: <OUT>/<NAME>_<VERSION>.zip]"
+    )]
+    pub zip: Option<Option<PathBuf>>,
+
+    #[arg(
+        long,
+        help = "Remove the html and latex docs and the tag file of a previous run before generating"
+    )]
+    pub clean: bool,
+
+    #[arg(
+        long,
+        help = "Only document the package's own sources, not those of its dependencies"
+    )]
+    pub no_deps: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with = "no_deps",
+        help = "Only document dependencies up to N steps away in the graph, 0 being just the package and 1 its direct dependencies [default: all]"
+    )]
+    pub depth: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Run conan source to fetch the package's sources when it has no sources folder"
+    )]
+    pub fetch_sources: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write a doxygen tag file other projects can link against"
+    )]
+    pub tagfile: Option<PathBuf>,
+
+    #[arg(
+        long = "tagfile-in",
+        value_name = "FILE=HTMLDIR",
+        value_parser = key_value,
+        help = "Link to the docs a tag file describes, may be repeated"
+    )]
+    pub tagfiles_in: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Folder to look for dependency docs in, as <DIR>/<name>/<name>.tag and <DIR>/<name>/html"
+    )]
+    pub tagfile_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Markdown file to use as the main page [default: the package's README.md]"
+    )]
+    pub mainpage: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Prefix to cut off the file paths shown in the docs, may be repeated [default: the folder containing all sources]"
+    )]
+    pub strip_from_path: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Image to show in the page header, png, jpg, gif or svg"
+    )]
+    pub logo: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "STR",
+        value_parser = non_empty,
+        help = "Version to show next to the project name [default: the conan version]"
+    )]
+    pub project_number: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "project_number",
+        help = "Show the output of git describe --tags --dirty in the package folder as the version, or the conan version outside a tagged checkout"
+    )]
+    pub git_describe: bool,
+
+    #[arg(
+        long,
+        help = "Don't add a page with a diagram of the package's dependencies"
+    )]
+    pub no_depgraph: bool,
+}
+
+impl GenerateOptions {
+    /// Options for documenting `src` with the same defaults as the CLI.
+    pub fn new(src: impl Into<PathBuf>) -> Self {
+        let cmd = Self::augment_args(clap::Command::new("conan-doxygen"));
+        let matches = cmd
+            .try_get_matches_from([OsString::from("conan-doxygen"), src.into().into()])
+            .expect("defaults are valid arguments");
+        Self::from_arg_matches(&matches).expect("defaults are valid arguments")
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+}
+
+/// The outcome of a documentation run.
+#[derive(Debug)]
+pub struct GenerateReport {
+    pub package: PackageInfo,
+    /// The folder doxygen writes into.
+    pub output: PathBuf,
+    /// The generated Doxyfile. It lives in a scratch directory that is
+    /// removed along with the report unless `keep` or `dry_run` was set.
+    pub doxyfile: PathBuf,
+    /// The folders doxygen was pointed at.
+    pub sources: Vec<PathBuf>,
+    /// Where doxygen wrote its warnings.
+    pub warnings_log: PathBuf,
+    /// Doxygen's exit status, `None` for dry runs and `doxyfile_only`, which
+    /// don't run it. A failing doxygen is
+    /// reported as [`Error::DoxygenFailed`] instead.
+    pub status: Option<ExitStatus>,
+    /// Number of warnings doxygen reported.
+    pub warnings: usize,
+    /// The generated entry points (e.g. html/index.html), empty unless doxygen
+    /// succeeded.
+    pub docs: Vec<PathBuf>,
+    /// The tag file written for `--tagfile`, if doxygen wrote one.
+    pub tagfile: Option<PathBuf>,
+    /// The zip of the html docs written for `--zip`.
+    pub archive: Option<PathBuf>,
+    /// The version `doxygen --version` reported, e.g. "1.9.8".
+    pub doxygen_version: Option<String>,
+    /// Keeps the Doxyfile around for as long as the report, e.g. for `watch`.
+    _scratch: ScratchDir,
+}
+
+impl GenerateReport {
+    /// The generated docs for `format`: html/index.html for html, and the PDF
+    /// for LaTeX, or the latex folder when it couldn't be built.
+    pub fn doc(&self, format: Format) -> Option<&Path> {
+        self.docs
+            .iter()
+            .find(|doc| doc.ends_with("html/index.html") == (format == Format::Html))
+            .map(PathBuf::as_path)
+    }
+
+    /// The report as JSON, for build systems consuming the results.
+    pub fn summary(&self) -> Value {
+        let html_index = self
+            .docs
+            .iter()
+            .find(|doc| doc.ends_with("html/index.html"));
+        json!({
+            "name": self.package.name,
+            "version": self.package.version,
+            "sources": self.sources,
+            "output": self.output,
+            "html_index": html_index,
+            "docs": self.docs,
+            "tagfile": self.tagfile,
+            "archive": self.archive,
+            "doxygen_version": self.doxygen_version,
+            "exit_code": self.status.and_then(|status| status.code()),
+            "warnings": self.warnings,
+        })
+    }
+}
+
+fn non_empty(value: &str) -> Result<String, String> {
+    if value.trim().is_empty() {
+        return Err(String::from("value must not be empty"));
+    }
+    Ok(value.to_string())
+}
+
+fn key_value(value: &str) -> Result<String, String> {
+    match value.split_once('=') {
+        Some((key, _)) if !key.trim().is_empty() => Ok(value.to_string()),
+        _ => Err(String::from("expected KEY=VALUE")),
+    }
+}
+
+fn filter_pattern(value: &str) -> Result<String, String> {
+    match value.split_once('=') {
+        Some((pattern, command)) if !pattern.trim().is_empty() && !command.trim().is_empty() => {
+            Ok(value.to_string())
+        }
+        _ => Err(String::from("expected PATTERN=CMD")),
+    }
+}
+
+/// Numbers the progress messages as `[n/total]`, where the total only counts
+/// the steps this run actually takes. Also keeps the time, for the ETA and
+/// `--deadline`.
+struct Steps {
+    current: usize,
+    total: usize,
+    started: Instant,
+    deadline: Option<Duration>,
+}
+
+/// One numbered step of a run, labelled like `[2/5] Gathering Sources...`.
+struct Step {
+    number: usize,
+    total: usize,
+    msg: String,
+    /// How much longer the run might take, going by the steps so far.
+    eta: Option<Duration>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}/{}] {}", self.number, self.total, self.msg)
+    }
+}
+
+impl Steps {
+    fn new(total: usize, started: Instant, deadline: Option<Duration>) -> Self {
+        Steps {
+            current: 0,
+            total,
+            started,
+            deadline,
+        }
+    }
+
+    /// Starts the next step, or fails once the deadline has passed.
+    fn next(&mut self, msg: &str) -> Result<Step> {
+        let elapsed = self.started.elapsed();
+        if let Some(deadline) = self.deadline.filter(|deadline| elapsed >= *deadline) {
+            return Err(Error::DeadlineExceeded {
+                seconds: deadline.as_secs(),
+            }
+            .into());
+        }
+        // Steps take very different times, so this is only a rough guide
+        let done = self.current as u32;
+        let eta = (done > 0).then(|| elapsed / done * (self.total as u32 - done));
+        self.current += 1;
+        Ok(Step {
+            number: self.current,
+            total: self.total,
+            msg: msg.to_string(),
+            eta,
+        })
+    }
+
+    /// `timeout`, cut short to what is left before the deadline.
+    fn timeout(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let left = self
+            .deadline
+            .map(|deadline| deadline.saturating_sub(self.started.elapsed()));
+        match (timeout, left) {
+            (Some(timeout), Some(left)) => Some(timeout.min(left)),
+            (timeout, left) => timeout.or(left),
+        }
+    }
+}
+
+/// How the steps of a run are reported.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Progress {
+    /// A spinner per step, with the time it took.
+    Spinner,
+    /// A bar filling up as the steps finish.
+    Bar,
+    /// A line when the step starts and one when it ends, for verbose runs and
+    /// logs.
+    Plain,
+    /// Nothing.
+    None,
+}
+
+fn with_progress_bar<F, T>(step: Step, progress: Progress, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<(String, T)>,
+{
+    if progress == Progress::None {
+        return f().map(|(_, val)| val);
+    }
+
+    // A steady tick would clobber streamed subprocess output
+    if progress == Progress::Plain {
+        println!("{}", step.to_string().yellow());
+        return match f() {
+            Ok((msg, val)) => {
+                println!("{}", msg.green());
+                Ok(val)
+            }
+            Err(e) => {
+                println!("Error: {}", e.to_string().red());
+                Err(e)
+            }
+        };
+    }
+
+    let (pb, msg) = if progress == Progress::Bar {
+        let pb = ProgressBar::new(step.total as u64);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner} {bar:20} {pos}/{len} {wide_msg} [{elapsed_precise}]")?,
+        );
+        pb.set_position(step.number as u64 - 1);
+        (pb, step.msg)
+    } else {
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner} {wide_msg} [{elapsed_precise}]")?,
+        );
+        (pb, step.to_string())
+    };
+
+    let msg = match step.eta.filter(|eta| eta.as_secs() > 0) {
+        Some(eta) => format!("{} (about {}s left)", msg, eta.as_secs()),
+        None => msg,
+    };
+    pb.enable_steady_tick(Duration::from_millis(50));
+    pb.set_message(format!("{}", msg.yellow()));
+    let res = f();
+    if res.is_ok() {
+        pb.inc(1);
+    }
+    // Finishing fills the bar, however many steps are left
+    let finish = |msg: String| {
+        if progress == Progress::Bar {
+            pb.abandon_with_message(msg)
+        } else {
+            pb.finish_with_message(msg)
+        }
+    };
+    match res {
+        Ok((msg, val)) => {
+            finish(format!("{}", msg.green()));
+            Ok(val)
+        }
+        Err(e) => {
+            finish(format!("Error: {}", e.to_string().red()));
+            Err(e)
+        }
+    }
+}
+
+fn preflight(runner: &dyn CommandRunner, conan: &str, doxygen: &Path) -> Result<()> {
+    runner
+        .resolve(Path::new(conan))
+        .ok_or_else(|| Error::ConanNotFound {
+            path: conan.to_string(),
+        })?;
+    runner
+        .resolve(doxygen)
+        .ok_or_else(|| Error::DoxygenNotFound {
+            path: doxygen.display().to_string(),
+        })?;
+    Ok(())
+}
+
+/// Points conan at `conan_home` when one is given. Conan 1 reads
+/// CONAN_USER_HOME and conan 2 CONAN_HOME, so both are set.
+fn conan_home_env(conan_home: Option<&Path>) -> Result<Vec<(String, String)>> {
+    let Some(conan_home) = conan_home else {
+        return Ok(Vec::new());
+    };
+    // Relative to where conan-doxygen was started, like --out
+    let conan_home = env::current_dir()?.join(conan_home);
+    let conan_home = conan_home
+        .to_str()
+        .ok_or_else(|| anyhow!("Failed to convert conan home to str"))?;
+    Ok(["CONAN_USER_HOME", "CONAN_HOME"]
+        .map(|name| (name.to_string(), conan_home.to_string()))
+        .to_vec())
+}
+
+/// `path` made absolute, resolving links when it exists. Paths that don't
+/// exist yet, as in dry runs, are taken relative to the current directory.
+fn absolute(path: &Path) -> Result<String> {
+    let path = match path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => env::current_dir()?.join(path),
+    };
+    Ok(path.display().to_string())
+}
+
+/// Creates the output folder and checks it can be written to, so a bad `--out`
+/// fails here rather than somewhere inside doxygen. Returns its canonical path.
+fn prepare_output(output: &Path, dry_run: bool) -> Result<PathBuf> {
+    // Relative to where the tool runs rather than to the package, while
+    // absolute paths are kept as they are
+    let output = env::current_dir()?.join(output);
+    if dry_run {
+        return Ok(output);
+    }
+    fs::create_dir_all(&output).map_err(|e| {
+        anyhow!(
+            "Failed to create output folder '{}': {}",
+            output.display(),
+            e
+        )
+    })?;
+    let output = output.canonicalize()?;
+    let probe = output.join(".conan-doxygen-write-check");
+    fs::write(&probe, "")
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| {
+            anyhow!(
+                "Output folder '{}' is not writable: {}",
+                output.display(),
+                e
+            )
+        })?;
+    Ok(output)
+}
+
+/// Where the docs go without --out: `build/docs/<name>_<version>` in the
+/// package folder. References have no package folder to put the docs in.
+fn default_output(src_pkg: &str, package: &PackageInfo) -> PathBuf {
+    let output_root = if is_reference(src_pkg) { "." } else { src_pkg };
+    Path::new(output_root)
+        .join("build")
+        .join("docs")
+        .join(format!("{}_{}", package.name, package.version))
+}
+
+/// Counts the files under `path`, which is what `--clean` reports removing.
+fn count_files(path: &Path) -> usize {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => count_files(&entry.path()),
+                    _ => 1,
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Removes what a previous run left in the output folder, so stale pages don't
+/// linger: the html and latex folders and the tag file. Refuses output
+/// folders that hold more than generated docs, e.g. the package or one of the
+/// gathered source folders, so a mistyped `--out` doesn't wipe out sources.
+fn clean_output(
+    output: &Path,
+    src_pkg: &str,
+    sources: &[String],
+    tagfile: Option<&Path>,
+    dry_run: bool,
+) -> Result<(String, ())> {
+    let Ok(output) = output.canonicalize() else {
+        return Ok((String::from("Nothing to clean"), ()));
+    };
+    let src = Path::new(src_pkg).canonicalize().ok();
+    let mut protected = vec![env::current_dir()?];
+    protected.extend(env::var_os("HOME").map(PathBuf::from));
+    protected.extend(src.clone());
+    let mut sources: Vec<PathBuf> = sources
+        .iter()
+        .filter_map(|source| Path::new(source).canonicalize().ok())
+        .collect();
+    sources.extend(src.clone());
+    // The default output is in the package's build folder, which may sit
+    // inside its sources
+    let build = src.map(|src| src.join("build"));
+    let in_build = |source: &Path| {
+        build
+            .as_ref()
+            .is_some_and(|build| output.starts_with(build) && build.starts_with(source))
+    };
+    if output.parent().is_none()
+        || protected.iter().any(|path| path.starts_with(&output))
+        || sources
+            .iter()
+            .any(|source| output.starts_with(source) && !in_build(source))
+    {
+        return Err(anyhow!(
+            "Refusing to clean '{}', it contains more than generated docs",
+            output.display()
+        ));
+    }
+
+    let mut generated: Vec<PathBuf> = ["html", "latex"]
+        .iter()
+        .map(|folder| output.join(folder))
+        .filter(|folder| folder.is_dir())
+        .collect();
+    generated.extend(
+        tagfile
+            .filter(|tagfile| tagfile.is_file())
+            .map(Path::to_path_buf),
+    );
+    if generated.is_empty() {
+        return Ok((String::from("Nothing to clean"), ()));
+    }
+    if dry_run {
+        for path in &generated {
+            println!("rm -r {}", path.display());
+        }
+        return Ok((String::from("Skipped cleaning output (dry run)"), ()));
+    }
+    let mut count = 0;
+    for path in &generated {
+        let removed = if path.is_dir() {
+            count += count_files(path);
+            fs::remove_dir_all(path)
+        } else {
+            count += 1;
+            fs::remove_file(path)
+        };
+        removed.map_err(|e| anyhow!("Failed to clean '{}': {}", path.display(), e))?;
+    }
+    Ok((
+        format!("Removed {} files from {}", count, output.display()),
+        (),
+    ))
+}
+
+/// Finds graphviz dot unless it was disabled. Doxygen falls back to its own
+/// class diagrams without it, so a missing dot is only worth a warning.
+fn resolve_dot(
+    runner: &dyn CommandRunner,
+    enable_dot: bool,
+    disable_dot: bool,
+    quiet: bool,
+) -> Option<PathBuf> {
+    if disable_dot {
+        return None;
+    }
+    let dot = runner.resolve(Path::new("dot"));
+    if dot.is_none() && enable_dot && !quiet {
+        println!(
+            "{}",
+            "graphviz dot not found in PATH, call graphs and collaboration diagrams will be skipped"
+                .yellow()
+        );
+    }
+    dot
+}
+
+/// Generates documentation for the conan package at `options.src`.
+pub fn generate(options: GenerateOptions) -> Result<GenerateReport, Error> {
+    generate_with(options, &SystemRunner)
+}
+
+/// Like [`generate`], but runs conan and doxygen through `runner`.
+pub fn generate_with(
+    options: GenerateOptions,
+    runner: &dyn CommandRunner,
+) -> Result<GenerateReport, Error> {
+    let started = Instant::now();
+    let deadline = options.deadline;
+    run_pipeline(options, runner, started).map_err(|e| match (deadline, Error::from(e)) {
+        // A program stopped because the deadline came before its own timeout
+        // reports the deadline
+        (Some(seconds), Error::TimedOut { .. })
+            if started.elapsed() >= Duration::from_secs(seconds) =>
+        {
+            Error::DeadlineExceeded { seconds }
+        }
+        (_, e) => e,
+    })
+}
+
+fn run_pipeline(
+    options: GenerateOptions,
+    runner: &dyn CommandRunner,
+    started: Instant,
+) -> Result<GenerateReport> {
+    let src_pkg = options
+        .src
+        .to_str()
+        .ok_or_else(|| anyhow!("Failed to convert source path to str"))?;
+    let conan = options.conan_path.as_str();
+    let doxygen = options
+        .doxygen_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("doxygen"));
+    preflight(runner, conan, &doxygen)?;
+    let doxygen = doxygen
+        .to_str()
+        .ok_or_else(|| anyhow!("Failed to convert doxygen path to str"))?;
+    // Settings doxygen doesn't know only show up as warnings in its log
+    let doxygen_version = doxygen_version(runner, doxygen);
+    if let Some(version) = doxygen_version.as_deref().filter(|_| !options.quiet) {
+        for setting in NEWER_SETTINGS
+            .iter()
+            .filter(|setting| (setting.requested)(&options) && predates(version, setting.since))
+        {
+            println!(
+                "{}",
+                format!(
+                    "doxygen {} predates {} (new in {}) and will ignore it for {}",
+                    version, setting.name, setting.since, setting.flag
+                )
+                .yellow()
+            );
+        }
+    }
+    let template = match &options.template {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read template '{}': {}", path.display(), e))?,
+        None => String::from(DOXYFILE_TEMPLATE),
+    };
+    // indicatif draws to stderr, and only when it is a terminal
+    let progress = match options.progress {
+        _ if options.quiet => Progress::None,
+        Some(Progress::None) => Progress::None,
+        _ if options.verbose || options.dry_run => Progress::Plain,
+        Some(progress) => progress,
+        None if io::stderr().is_terminal() => Progress::Spinner,
+        None => Progress::Plain,
+    };
+    let dot = resolve_dot(
+        runner,
+        options.enable_dot,
+        options.disable_dot,
+        options.quiet,
+    );
+    // Without dot doxygen silently leaves the graphs out
+    let graph_flag = [
+        (options.call_graph, "--call-graph"),
+        (options.caller_graph, "--caller-graph"),
+    ]
+    .into_iter()
+    .find_map(|(set, flag)| set.then_some(flag));
+    if let (None, Some(flag)) = (&dot, graph_flag) {
+        return Err(Error::DotNotFound {
+            flag: flag.to_string(),
+        }
+        .into());
+    }
+    if let Some(logo) = &options.logo {
+        check_logo(logo)?;
+    }
+    let build_type_set = options.settings.iter().any(|setting| {
+        setting
+            .split_once('=')
+            .is_some_and(|(key, _)| key.trim() == "build_type")
+    });
+    if options.build_type.is_some() && build_type_set {
+        return Err(anyhow!(
+            "--build-type and --setting build_type=... can't be combined"
+        ));
+    }
+    let lockfile = match &options.lockfile {
+        // Reading it also rules out folders
+        Some(lockfile) => Some(
+            fs::read(lockfile)
+                .and_then(|_| lockfile.canonicalize())
+                .map_err(|e| anyhow!("Failed to read lockfile '{}': {}", lockfile.display(), e))?,
+        ),
+        None => None,
+    };
+    if options.zip.is_some() && !options.format.contains(&Format::Html) {
+        return Err(anyhow!(
+            "--zip packs the html docs, add html to --format to use it"
+        ));
+    }
+    // Dry runs point at the Doxyfile, so it has to outlive the run
+    let scratch = ScratchDir::new(options.keep || options.dry_run || options.doxyfile_only)?;
+    let install_folder = scratch.path().join("conan");
+    let doxy_folder = scratch.path().join("doxy");
+    let env_runner = WithEnv {
+        runner,
+        env: options
+            .env
+            .iter()
+            .filter_map(|var| var.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    };
+    let runner: &dyn CommandRunner = &env_runner;
+    let conan_runner = WithEnv {
+        runner,
+        env: conan_home_env(options.conan_home.as_deref())?,
+    };
+
+    let building_pdf =
+        options.format.contains(&Format::Latex) && !options.dry_run && !options.doxyfile_only;
+    let zipping = options.zip.is_some() && !options.dry_run && !options.doxyfile_only;
+    // The hooks go with the doxygen step
+    let hooks = if options.doxyfile_only {
+        0
+    } else {
+        usize::from(options.pre_hook.is_some()) + usize::from(options.post_hook.is_some())
+    };
+    let mut steps = Steps::new(
+        5 + usize::from(options.clean) + usize::from(building_pdf) + usize::from(zipping) + hooks
+            - usize::from(options.doxyfile_only)
+            - usize::from(options.no_install),
+        started,
+        options.deadline.map(Duration::from_secs),
+    );
+
+    // conan inspect
+    let package = inspect(
+        &conan_runner,
+        conan,
+        src_pkg,
+        &scratch.path().join("inspect.json"),
+        steps.timeout(None),
+        options.verbose,
+        options.dry_run,
+    )?;
+    if !options.quiet {
+        println!(
+            "Generating documentation for {}/{} with \n {:#?}",
+            package.name.green(),
+            package.version.green(),
+            package
+                .requires
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    let mut settings = options.settings.clone();
+    settings.extend(
+        options
+            .build_type
+            .iter()
+            .map(|build_type| format!("build_type={}", build_type)),
+    );
+
+    // conan install
+    let install_options = InstallOptions {
+        profile: &options.profile,
+        installer: options.installer.as_deref(),
+        build_specs: &options.build_specs,
+        settings: &settings,
+        options: &options.options,
+        install_folder: &install_folder,
+        timeout: steps.timeout(options.timeout()),
+        force: options.force_install,
+        fetch_sources: options.fetch_sources,
+        install_log: options.install_log.as_deref(),
+        lockfile: lockfile.as_deref(),
+    };
+    if !options.no_install {
+        with_progress_bar(steps.next("Fetching packages...")?, progress, || {
+            conan_install(
+                &conan_runner,
+                conan,
+                src_pkg,
+                &install_options,
+                options.verbose,
+                options.dry_run,
+            )
+        })?;
+    }
+
+    // conan info
+    let source_folders = with_progress_bar(steps.next("Gathering Sources...")?, progress, || {
+        gather_sources(
+            &conan_runner,
+            conan,
+            src_pkg,
+            &InstallOptions {
+                timeout: steps.timeout(None),
+                ..install_options
+            },
+            // --no-deps is the same as --depth 0
+            options.no_deps.then_some(0).or(options.depth),
+            options.verbose,
+            options.dry_run,
+        )
+        .map_err(|e| match e.downcast::<Error>() {
+            Ok(Error::NoSourcesFound { src }) if options.no_install => {
+                Error::NotInstalled { src }.into()
+            }
+            Ok(e) => e.into(),
+            Err(e) => e,
+        })
+    })?;
+    // The Doxyfile may be kept and run from elsewhere
+    let source_folders = source_folders
+        .iter()
+        .map(|folder| absolute(Path::new(folder)))
+        .collect::<Result<Vec<String>>>()?;
+
+    // output path
+    let output = with_progress_bar(steps.next("Resolving Output...")?, progress, || {
+        let output = prepare_output(
+            &options
+                .out
+                .clone()
+                .unwrap_or_else(|| default_output(src_pkg, &package)),
+            options.dry_run,
+        )?;
+        Ok((format!("Output location is {}", output.display()), output))
+    })?;
+
+    if options.clean {
+        with_progress_bar(steps.next("Cleaning output...")?, progress, || {
+            clean_output(
+                &output,
+                src_pkg,
+                &source_folders,
+                options.tagfile.as_deref(),
+                options.dry_run,
+            )
+        })?;
+    }
+
+    // Generate DoxyFile
+    let mut tagfiles = options.tagfiles_in.clone();
+    if let Some(dir) = &options.tagfile_dir {
+        tagfiles.extend(discover_tagfiles(dir, &package.requires));
+    }
+    // A package's README, next to its sources or its conanfile
+    let mainpage =
+        match &options.mainpage {
+            Some(mainpage) => Some(mainpage.canonicalize().map_err(|e| {
+                anyhow!("Failed to find main page '{}': {}", mainpage.display(), e)
+            })?),
+            None if !is_reference(src_pkg) => {
+                let src = Path::new(src_pkg);
+                find_mainpage(&[src.join("sources"), src.to_path_buf()])
+                    .and_then(|mainpage| mainpage.canonicalize().ok())
+            }
+            None => None,
+        };
+    let strip_from_path: Vec<String> = if options.strip_from_path.is_empty() {
+        common_prefix(&source_folders).into_iter().collect()
+    } else {
+        options.strip_from_path.clone()
+    }
+    .iter()
+    .map(|path| absolute(path))
+    .collect::<Result<_>>()?;
+    // Headers are looked up in the packages' include folders as well, so
+    // macros they define get expanded
+    let include_paths: Vec<String> = options
+        .include_paths
+        .iter()
+        .map(|path| absolute(path))
+        .chain(subfolders(&source_folders, "include").into_iter().map(Ok))
+        .collect::<Result<_>>()?;
+    // Examples may also sit next to the conanfile rather than the sources
+    let mut example_folders = source_folders.clone();
+    if !is_reference(src_pkg) {
+        example_folders.push(absolute(Path::new(src_pkg))?);
+    }
+    let example_paths: Vec<String> = options
+        .example_paths
+        .iter()
+        .map(|path| absolute(path))
+        .chain(subfolders(&example_folders, "examples").into_iter().map(Ok))
+        .collect::<Result<_>>()?;
+    let image_paths: Vec<String> = options
+        .image_paths
+        .iter()
+        .map(|path| absolute(path))
+        .collect::<Result<_>>()?;
+    let project_number = match &options.project_number {
+        Some(project_number) => project_number.clone(),
+        None if options.git_describe && !is_reference(src_pkg) => {
+            git::describe(runner, Path::new(src_pkg), options.verbose, options.dry_run)
+                .unwrap_or_else(|| package.version.clone())
+        }
+        None => package.version.clone(),
+    };
+    let warnings_log = options
+        .warnings_log
+        .clone()
+        .unwrap_or_else(|| doxy_folder.join("warnings.log"));
+    let doxyfile_options = DoxyfileOptions {
+        formats: &options.format,
+        exclude_patterns: &options.exclude_patterns,
+        exclude_symbols: if options.no_exclude_symbols {
+            &[]
+        } else {
+            &options.exclude_symbols
+        },
+        file_patterns: &options.file_patterns,
+        defines: &options.defines,
+        include_paths: &include_paths,
+        example_paths: &example_paths,
+        image_paths: &image_paths,
+        aliases: &options.aliases,
+        input_filter: options.input_filter.as_deref(),
+        filter_patterns: &options.filter_patterns,
+        warnings_log: &warnings_log,
+        dot: dot.as_deref(),
+        doxy_folder: &doxy_folder,
+        tagfile: options.tagfile.as_deref(),
+        tagfiles: &tagfiles,
+        mainpage: mainpage.as_deref(),
+        strip_from_path: &strip_from_path,
+        logo: options.logo.as_deref(),
+        language: &options.language,
+        project_number: &project_number,
+        theme: options.theme,
+        optimize_for: options.optimize_for,
+        generate_treeview: options.generate_treeview,
+        full_sidebar: options.full_sidebar,
+        extract_all: !options.no_extract_all,
+        extract_private: options.extract_private,
+        extract_static: options.extract_static,
+        internal_docs: options.internal_docs,
+        source_browser: options.source_browser,
+        inline_sources: options.inline_sources,
+        call_graph: options.call_graph,
+        caller_graph: options.caller_graph,
+        depgraph: !options.no_depgraph,
+        template: &template,
+        dry_run: options.dry_run,
+    };
+    let doxy_file_out = with_progress_bar(steps.next("Generating Doxyfile...")?, progress, || {
+        generate_doxyfile(&package, &source_folders, &output, &doxyfile_options)
+    })?;
+
+    if let Some(hook) = options
+        .pre_hook
+        .as_deref()
+        .filter(|_| !options.doxyfile_only)
+    {
+        let hook_timeout = steps.timeout(None);
+        let printed = with_progress_bar(steps.next("Running pre-hook...")?, progress, || {
+            run_hook(
+                runner,
+                hook,
+                &package,
+                &output,
+                hook_timeout,
+                options.verbose,
+                options.dry_run,
+            )
+        })?;
+        if !options.quiet {
+            print!("{}", printed);
+        }
+    }
+
+    // Doxygen generate
+    let result = if options.doxyfile_only {
+        None
+    } else {
+        let doxygen_timeout = steps.timeout(options.timeout());
+        with_progress_bar(steps.next("Running Doxygen...")?, progress, || {
+            run_doxygen(
+                runner,
+                doxygen,
+                &doxy_file_out,
+                &warnings_log,
+                doxygen_timeout,
+                options.verbose,
+                options.dry_run,
+            )
+        })?
+    };
+    let (status, warnings) = match result {
+        Some((status, warnings)) => (Some(status), warnings),
+        None => (None, 0),
+    };
+    if warnings > 0 && options.warnings_log.is_some() && !options.quiet {
+        println!("  Warnings saved to {}", warnings_log.display());
+    }
+    if options.keep && !options.dry_run && !options.quiet {
+        println!("  Scratch files kept in {}", scratch.path().display());
+    }
+
+    if let Some(status) = status.filter(|status| !status.success()) {
+        return Err(Error::DoxygenFailed { status, warnings }.into());
+    }
+
+    let mut docs = Vec::new();
+    if status.is_some() {
+        if options.format.contains(&Format::Html) {
+            docs.push(output.join("html").join("index.html"));
+        }
+        if options.format.contains(&Format::Latex) {
+            let pdf = with_progress_bar(steps.next("Building PDF...")?, progress, || {
+                build_pdf(runner, &output, options.verbose)
+            })?;
+            docs.push(pdf.unwrap_or_else(|| output.join("latex")));
+        }
+        docs = docs
+            .into_iter()
+            .map(|doc| doc.canonicalize())
+            .collect::<Result<_, _>>()?;
+    }
+
+    let archive = match &options.zip {
+        Some(path) if status.is_some() => {
+            let default = || output.join(format!("{}_{}.zip", package.name, package.version));
+            let archive = path.clone().unwrap_or_else(default);
+            Some(with_progress_bar(
+                steps.next("Packing html docs...")?,
+                progress,
+                || zip_folder(&output.join("html"), "html", &archive),
+            )?)
+        }
+        _ => None,
+    };
+
+    // Dry runs show the command like they do for doxygen
+    let ran = status.is_some() || (options.dry_run && !options.doxyfile_only);
+    if let Some(hook) = options.post_hook.as_deref().filter(|_| ran) {
+        let hook_timeout = steps.timeout(None);
+        let printed = with_progress_bar(steps.next("Running post-hook...")?, progress, || {
+            run_hook(
+                runner,
+                hook,
+                &package,
+                &output,
+                hook_timeout,
+                options.verbose,
+                options.dry_run,
+            )
+        })?;
+        if !options.quiet {
+            print!("{}", printed);
+        }
+    }
+
+    let tagfile = match &options.tagfile {
+        Some(tagfile) if status.is_some() => tagfile.canonicalize().ok(),
+        _ => None,
+    };
+
+    Ok(GenerateReport {
+        package,
+        output,
+        doxyfile: doxy_file_out,
+        sources: source_folders.into_iter().map(PathBuf::from).collect(),
+        warnings_log,
+        status,
+        warnings,
+        docs,
+        tagfile,
+        archive,
+        doxygen_version,
+        _scratch: scratch,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeRunner;
+
+    /// A local recipe with its sources in `pkg/sources`, and a dependency in
+    /// `cache/fmt`, below a scratch folder.
+    fn package_fixture() -> (ScratchDir, PathBuf, PathBuf) {
+        let root = ScratchDir::new(false).unwrap();
+        let pkg = root.path().join("pkg");
+        let fmt = root.path().join("cache").join("fmt");
+        fs::create_dir_all(pkg.join("sources").join("include")).unwrap();
+        fs::create_dir_all(fmt.join("include")).unwrap();
+        fs::write(pkg.join("conanfile.py"), "").unwrap();
+        fs::write(pkg.join("sources").join("include").join("pkg.h"), "").unwrap();
+        fs::write(fmt.join("include").join("fmt.h"), "").unwrap();
+        (root, pkg, fmt)
+    }
+
+    fn fake_options(src: &Path, out: &Path) -> GenerateOptions {
+        GenerateOptions {
+            out: Some(out.to_path_buf()),
+            progress: Some(Progress::None),
+            quiet: true,
+            ..GenerateOptions::new(src)
+        }
+    }
+
+    /// Writes the html entry point into the Doxyfile's OUTPUT_DIRECTORY, like
+    /// doxygen would.
+    fn fake_doxygen(doxyfile: &str) -> (i32, String) {
+        let doxyfile = fs::read_to_string(doxyfile).unwrap();
+        let output = doxyfile
+            .lines()
+            .find_map(|line| line.strip_prefix("OUTPUT_DIRECTORY"))
+            .and_then(|rest| rest.trim_start().strip_prefix('='))
+            .map(|value| value.trim().trim_matches('"'))
+            .unwrap();
+        let html = Path::new(output).join("html");
+        fs::create_dir_all(&html).unwrap();
+        fs::write(html.join("index.html"), "").unwrap();
+        (0, String::new())
+    }
+
+    #[test]
+    fn generates_docs_from_conan_1_output() {
+        let (root, pkg, fmt) = package_fixture();
+        let fmt_folder = fmt.display().to_string();
+        let runner = FakeRunner::new(|program, args| match (program, args) {
+            ("conan", ["--version"]) => (0, String::from("Conan version 1.62.0\n")),
+            ("conan", ["inspect", _, "--json", json_file, ..]) => {
+                let json = r#"{"name": "mypkg", "version": "1.2.3", "requires": ["fmt/9.1.0"]}"#;
+                fs::write(json_file, json).unwrap();
+                (0, String::new())
+            }
+            ("conan", ["info", ..]) => {
+                let nodes = json!([
+                    { "reference": "conanfile.py" },
+                    { "reference": "fmt/9.1.0", "package_folder": fmt_folder },
+                ]);
+                (0, format!("WARN: a banner\n{}\n\n", nodes))
+            }
+            ("doxygen", ["--version"]) => (0, String::from("1.9.8 (c2fe5c0)\n")),
+            ("doxygen", [doxyfile]) => fake_doxygen(doxyfile),
+            _ => (1, String::new()),
+        });
+        let out = root.path().join("out");
+
+        let report = generate_with(fake_options(&pkg, &out), &runner).unwrap();
+
+        assert_eq!(report.package.name, "mypkg");
+        assert_eq!(report.package.version, "1.2.3");
+        assert_eq!(report.doxygen_version.as_deref(), Some("1.9.8"));
+        let mut sources = report.sources.clone();
+        sources.sort();
+        let mut expected = vec![
+            fmt.canonicalize().unwrap(),
+            pkg.join("sources").canonicalize().unwrap(),
+        ];
+        expected.sort();
+        assert_eq!(sources, expected);
+        assert_eq!(
+            report.docs,
+            [out.join("html").join("index.html").canonicalize().unwrap()]
+        );
+        let doxyfile = fs::read_to_string(&report.doxyfile).unwrap();
+        assert!(doxyfile.contains(&format!("\"{}\"", fmt.canonicalize().unwrap().display())));
+        // Everything is in the cache already, so there is nothing to install
+        let calls = runner.calls();
+        assert!(calls.iter().any(|call| call.starts_with("conan info")));
+        assert!(!calls.iter().any(|call| call.starts_with("conan install")));
+    }
+
+    #[test]
+    fn documents_a_reference_with_conan_2() {
+        let (root, _, fmt) = package_fixture();
+        let fmt_folder = fmt.display().to_string();
+        let runner = FakeRunner::new(|program, args| match (program, args) {
+            ("conan", ["--version"]) => (0, String::from("Conan version 2.0.5\n")),
+            ("conan", ["graph", "info", ..]) => {
+                let graph = json!({ "graph": { "nodes": {
+                    "1": { "id": "1", "ref": "fmt/9.1.0", "package_folder": fmt_folder },
+                } } });
+                (0, graph.to_string())
+            }
+            ("doxygen", ["--version"]) => (0, String::from("1.9.8\n")),
+            ("doxygen", [doxyfile]) => fake_doxygen(doxyfile),
+            _ => (1, String::new()),
+        });
+        let out = root.path().join("out");
+
+        let report = generate_with(fake_options(Path::new("fmt/9.1.0@"), &out), &runner).unwrap();
+
+        assert_eq!(report.package.name, "fmt");
+        assert_eq!(report.package.version, "9.1.0");
+        assert_eq!(report.sources, [fmt.canonicalize().unwrap()]);
+        let calls = runner.calls();
+        assert!(calls.contains(&String::from(
+            "conan graph info --requires=fmt/9.1.0 --format json -pr default"
+        )));
+        assert!(!calls.iter().any(|call| call.starts_with("conan inspect")));
+    }
+
+    #[test]
+    fn reports_missing_doxygen_before_running_anything() {
+        let (root, pkg, _) = package_fixture();
+        let runner = FakeRunner::new(|_, _| (0, String::new())).without("doxygen");
+
+        let result = generate_with(fake_options(&pkg, &root.path().join("out")), &runner);
+
+        assert!(matches!(result, Err(Error::DoxygenNotFound { .. })));
+        assert!(runner.calls().is_empty());
+    }
+
+    /// Answers like `runner`, except that `conan info` takes until its timeout
+    /// runs out. It is then stopped, or with `stopped` unset fails on its own.
+    struct SlowInfo<R> {
+        runner: R,
+        stopped: bool,
+    }
+
+    impl<R: CommandRunner> CommandRunner for SlowInfo<R> {
+        fn run(&self, program: &str, args: &[&str]) -> Result<std::process::Output> {
+            self.run_timeout(program, args, None)
+        }
+
+        fn run_timeout(
+            &self,
+            program: &str,
+            args: &[&str],
+            timeout: Option<Duration>,
+        ) -> Result<std::process::Output> {
+            if args.first() != Some(&"info") {
+                return self.runner.run(program, args);
+            }
+            let timeout = timeout.expect("conan info runs with a timeout");
+            std::thread::sleep(timeout);
+            if !self.stopped {
+                return self.runner.run(program, args);
+            }
+            Err(Error::TimedOut {
+                program: program.to_string(),
+                seconds: timeout.as_secs(),
+            }
+            .into())
+        }
+
+        fn resolve(&self, program: &Path) -> Option<PathBuf> {
+            self.runner.resolve(program)
+        }
+    }
+
+    /// Runs a local recipe with a deadline of a second and `conan info`
+    /// taking all of it.
+    fn run_past_deadline(stopped: bool) -> Result<GenerateReport, Error> {
+        let (root, pkg, _) = package_fixture();
+        let runner = SlowInfo {
+            runner: FakeRunner::new(|_, args| match args {
+                ["--version"] => (0, String::from("Conan version 1.62.0\n")),
+                ["inspect", _, "--json", json_file, ..] => {
+                    fs::write(json_file, r#"{"name": "mypkg", "version": "1.2.3"}"#).unwrap();
+                    (0, String::new())
+                }
+                _ => (1, String::new()),
+            }),
+            stopped,
+        };
+        let options = GenerateOptions {
+            deadline: Some(1),
+            no_install: true,
+            ..fake_options(&pkg, &root.path().join("out"))
+        };
+        generate_with(options, &runner)
+    }
+
+    #[test]
+    fn stops_a_hanging_conan_info_at_the_deadline() {
+        let result = run_past_deadline(true);
+
+        assert!(
+            matches!(result, Err(Error::DeadlineExceeded { seconds: 1 })),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn keeps_failures_that_come_after_the_deadline() {
+        let result = run_past_deadline(false);
+
+        let error = result.unwrap_err();
+        assert!(error.to_string().contains("conan info"), "{}", error);
+    }
+
+    #[test]
+    fn resolves_a_relative_output_against_the_current_directory() {
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(
+            prepare_output(Path::new("docs"), true).unwrap(),
+            cwd.join("docs")
+        );
+
+        let scratch = ScratchDir::new(false).unwrap();
+        let absolute = scratch.path().join("docs");
+        assert_eq!(prepare_output(&absolute, true).unwrap(), absolute);
+        assert_eq!(
+            prepare_output(&absolute, false).unwrap(),
+            absolute.canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn defaults_the_output_to_the_package_build_folder() {
+        let package = PackageInfo::bare(String::from("mypkg"), String::from("1.2.3"));
+        let components = |path: PathBuf| {
+            path.components()
+                .map(|part| part.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            components(default_output("pkg", &package)),
+            ["pkg", "build", "docs", "mypkg_1.2.3"]
+        );
+        assert_eq!(
+            components(default_output("mypkg/1.2.3@", &package)),
+            [".", "build", "docs", "mypkg_1.2.3"]
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn builds_windows_paths_with_backslashes() {
+        let package = PackageInfo::bare(String::from("mypkg"), String::from("1.2.3"));
+        assert_eq!(
+            default_output(r"C:\work\pkg\", &package)
+                .display()
+                .to_string(),
+            r"C:\work\pkg\build\docs\mypkg_1.2.3"
+        );
+
+        let scratch = ScratchDir::new(false).unwrap();
+        let doxy_folder = scratch.path().join("doxy").display().to_string();
+        assert!(!doxy_folder.contains('/'), "{}", doxy_folder);
+    }
+}
@@ -1,230 +1,600 @@
 use std::{
-    collections::HashMap,
-    fs::{self, File},
-    path::PathBuf,
-    process::{Command, Stdio},
-    result::Result::Ok,
+    env, fmt, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
     time::Duration,
 };
 
 use anyhow::{anyhow, Result};
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
-use handlebars::Handlebars;
-use indicatif::{ProgressBar, ProgressStyle};
+use conan_doxygen::{
+    completions, doctor, generate, handle_interrupts, serve, watch, write_index, Format,
+    GenerateOptions, GenerateReport, Progress, Shell, SystemRunner,
+};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use opener::open;
-use serde_json::Value;
+use serde_json::{json, Value};
 
+/// Generate documentation for conan packages using doxygen
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Arguments {
-    #[arg(help = "Path to conan package")]
-    src: PathBuf,
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    options: Option<GenerateOptions>,
 
-    #[arg(long, help = "Path to output folder")]
-    out: Option<PathBuf>,
+    #[arg(
+        value_name = "SRC",
+        help = "More packages to document in the same run, each into its own output folder"
+    )]
+    more_srcs: Vec<PathBuf>,
 
     #[arg(long, help = "Open generated documentation")]
     open: bool,
+
+    #[arg(
+        long,
+        value_name = "CMD",
+        help = "Program to open the docs with instead of the default, e.g. firefox, implies --open"
+    )]
+    open_with: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        help = "Which docs to open when several formats are generated, implies --open [default: the first generated]"
+    )]
+    open_format: Option<Format>,
+
+    #[arg(
+        long,
+        short = 'j',
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "How many packages to document at once"
+    )]
+    jobs: u16,
+
+    #[arg(long, help = "Exit with an error if doxygen produced any warnings")]
+    fail_on_warnings: bool,
+
+    #[arg(
+        long,
+        value_name = "PORT",
+        num_args = 0..=1,
+        default_missing_value = "8000",
+        help = "Serve the html docs on localhost and open them in the browser [default port: 8000]"
+    )]
+    serve: Option<u16>,
+
+    #[arg(
+        long,
+        help = "Run doxygen again whenever the sources change, until stopped"
+    )]
+    watch: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["verbose", "dry_run"],
+        help = "Print a JSON summary of the run instead of progress, including errors"
+    )]
+    json: bool,
+
+    #[arg(
+        long,
+        help = "Print the settings a run would use and where each comes from, without generating anything"
+    )]
+    print_config: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["json", "verbose", "dry_run"],
+        help = "Print the generated Doxyfile instead of running doxygen, e.g. to pipe into doxygen -. The files it points at are kept in the scratch directory"
+    )]
+    print_doxyfile: bool,
 }
 
-fn with_progress_bar<F, T>(msg: String, f: F) -> Result<T>
-where
-    F: FnOnce() -> Result<(String, T)>,
-{
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner().template("{spinner} {wide_msg} [{elapsed_precise}]")?,
-    );
-
-    pb.enable_steady_tick(Duration::from_millis(50));
-    pb.set_message(format!("{}", msg.yellow()));
-    let res = f();
-    match res {
-        Ok((msg, val)) => {
-            pb.finish_with_message(format!("{}", msg.green()));
-            Ok(val)
-        }
-        Err(e) => {
-            pb.finish_with_message(format!("Error: {}", e.to_string().red()));
-            Err(e)
-        }
+impl Arguments {
+    fn wants_open(&self) -> bool {
+        self.open || self.open_with.is_some() || self.open_format.is_some()
     }
 }
 
-fn gather_sources(src_pkg: &str) -> Result<(String, Vec<String>)> {
-    let info_output_raw = Command::new("conan")
-        .args(["info", src_pkg, "--paths", "--json"])
-        .output()?
-        .stdout;
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Check that conan, doxygen and graphviz are available
+    Doctor {
+        #[arg(long, default_value = "conan", help = "Path to conan executable")]
+        conan_path: String,
 
-    let info_output_raw_str = String::from_utf8(info_output_raw)?;
-    let temp = info_output_raw_str.split('\n').collect::<Vec<&str>>();
-    let info_json_raw = temp.last().ok_or(anyhow!("Failed to get package paths"))?;
-    let info_json_obj: Vec<Value> = serde_json::from_str(info_json_raw)?;
-    let mut source_folders = Vec::new();
-    for obj in info_json_obj {
-        match obj.get("package_folder") {
-            Some(val) => {
-                if let Some(s) = val.as_str() {
-                    source_folders.push(s.to_string());
-                }
-            }
-            None => continue,
+        #[arg(long, default_value = "doxygen", help = "Path to doxygen executable")]
+        doxygen_path: PathBuf,
+    },
+    /// Print a completion script for a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Opens `target` with `open_with`, which may include arguments, or with the
+/// default handler.
+fn open_in(target: &str, open_with: Option<&str>) -> Result<()> {
+    let Some(open_with) = open_with else {
+        return Ok(open(target)?);
+    };
+    let mut words = open_with.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| anyhow!("--open-with must not be empty"))?;
+    process::Command::new(program)
+        .args(words)
+        .arg(target)
+        .spawn()?;
+    Ok(())
+}
+
+fn report_docs(
+    report: &GenerateReport,
+    open_docs: bool,
+    open_format: Option<Format>,
+    open_with: Option<&str>,
+    quiet: bool,
+) -> Result<()> {
+    if report.status.is_none() {
+        println!(
+            "\n Doxyfile can be found at {}",
+            report.doxyfile.display().to_string().green()
+        );
+        return Ok(());
+    }
+
+    if !quiet {
+        println!();
+    }
+    for doc in &report.docs {
+        println!(
+            " Success: Docs can be found at {}",
+            doc.display().to_string().green()
+        );
+    }
+    if let Some(tagfile) = &report.tagfile {
+        println!(
+            " Tag file can be found at {}",
+            tagfile.display().to_string().green()
+        );
+    }
+    if let Some(archive) = &report.archive {
+        println!(
+            " Archive can be found at {}",
+            archive.display().to_string().green()
+        );
+    }
+
+    if open_docs {
+        let doc = match open_format {
+            Some(format) => report.doc(format),
+            None => report.docs.first().map(PathBuf::as_path),
+        };
+        let Some(doc) = doc else {
+            return Ok(());
+        };
+        let doc = doc
+            .to_str()
+            .ok_or_else(|| anyhow!("Failed to convert docs path to str"))?;
+        match open_in(doc, open_with) {
+            Ok(()) if quiet => {}
+            Ok(()) => println!("Opened '{}' successfully.", doc),
+            Err(err) => eprintln!("An error occurred when opening '{}': {}", doc, err),
         }
     }
 
-    source_folders.push(format!("{}/sources", src_pkg));
-    Ok((
-        format!("Found {} source locations", source_folders.len()),
-        source_folders,
-    ))
+    Ok(())
 }
 
-fn conan_install(src_pkg: &str) -> Result<(String, ())> {
-    let install_folder = format!("{}/.conan", src_pkg );
-    Command::new("cdt")
-        .args(["conan", "install", src_pkg, "-pr", "default", "-if", install_folder.as_str() ])
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()?;
-    Ok((String::from("Finished conan install"), ()))
+/// `--fail-on-warnings` tripped. The docs were generated all the same, so
+/// the report comes along for the summary.
+#[derive(Debug)]
+struct FailedOnWarnings(GenerateReport);
+
+impl fmt::Display for FailedOnWarnings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Doxygen produced {} warnings and --fail-on-warnings is set",
+            self.0.warnings
+        )
+    }
 }
 
-fn inspect(src_pkg: &str) -> Result<(String, String, Vec<String>)> {
-    let name_bytes = Command::new("conan")
-        .args(["inspect", src_pkg, "--raw", "name"])
-        .output()?
-        .stdout;
+impl std::error::Error for FailedOnWarnings {}
 
-    let version_bytes = Command::new("conan")
-        .args(["inspect", src_pkg, "--raw", "version"])
-        .output()?
-        .stdout;
+/// What `--json` prints for a failed run: the error, along with the summary
+/// when the docs were generated before it failed.
+fn error_json(err: &anyhow::Error) -> Value {
+    let mut json = match err.downcast_ref::<FailedOnWarnings>() {
+        Some(FailedOnWarnings(report)) => report.summary(),
+        None => json!({}),
+    };
+    json["error"] = json!(format!("{:#}", err));
+    json
+}
 
-    let requires_bytes = Command::new("conan")
-        .args(["inspect", src_pkg, "--raw", "requires"])
-        .output()?
-        .stdout;
+fn serve_docs(html: &Path, port: u16, open_with: Option<&str>) -> Result<()> {
+    if !html.is_dir() {
+        return Err(anyhow!(
+            "No html docs to serve, add html to --format to use --serve"
+        ));
+    }
+    serve(html, port, |url| {
+        println!("\n Serving docs at {} (press Ctrl-C to stop)", url.green());
+        if let Err(err) = open_in(url, open_with) {
+            eprintln!("An error occurred when opening '{}': {}", url, err);
+        }
+    })
+}
+
+fn document(mut options: GenerateOptions, args: &Arguments, matches: &ArgMatches) -> Result<()> {
+    // Nothing but the summary may go to stdout for --json
+    options.quiet |= args.json;
+    options.apply_config(matches)?;
+    if let Some(format) = args.open_format {
+        if !options.format.contains(&format) {
+            let name = format.to_possible_value().expect("no skipped formats");
+            return Err(anyhow!(
+                "--open-format {0} needs {0} among the --format values",
+                name.get_name()
+            ));
+        }
+    }
+    // Without a doxygen run there are no docs to serve or rebuild
+    let skip_doxygen = options.dry_run || options.doxyfile_only;
+    let serve_port = args.serve.filter(|_| !skip_doxygen);
+    let watch_sources = args.watch && !skip_doxygen;
+    let watch_options = options.clone();
+    let report = generate(options)?;
+    let html = report.output.join("html");
+    let failed_on_warnings = args.fail_on_warnings && report.warnings > 0;
+    if !args.json {
+        report_docs(
+            &report,
+            args.wants_open() && serve_port.is_none(),
+            args.open_format,
+            args.open_with.as_deref(),
+            watch_options.quiet,
+        )?;
+    } else if !failed_on_warnings {
+        println!("{}", report.summary());
+    }
 
-    let name = String::from_utf8(name_bytes)?;
-    let version = String::from_utf8(version_bytes)?;
-    let requires = String::from_utf8(requires_bytes)?
-        .split(',')
-        .map(|s| s.trim_start_matches('['))
-        .map(|s| s.trim_end_matches(']'))
-        .map(|s| s.trim().replace('\'', ""))
-        .collect::<Vec<String>>();
+    if failed_on_warnings {
+        return Err(FailedOnWarnings(report).into());
+    }
 
-    Ok((name, version, requires))
+    match (serve_port, watch_sources) {
+        (Some(port), true) => {
+            let open_with = args.open_with.clone();
+            thread::spawn(move || {
+                if let Err(err) = serve_docs(&html, port, open_with.as_deref()) {
+                    eprintln!("Error: {}", err);
+                }
+            });
+            watch(&report, &watch_options, &SystemRunner)
+        }
+        (None, true) => watch(&report, &watch_options, &SystemRunner),
+        (Some(port), false) => serve_docs(&html, port, args.open_with.as_deref()),
+        (None, false) => Ok(()),
+    }
 }
 
-fn generate_doxyfile(
-    name: &String,
-    version: &String,
-    sources_str: &String,
-    output_str: &String,
-) -> Result<(String, PathBuf)> {
-    let mut handlebars = Handlebars::new();
-    let mut handlebar_data = HashMap::new();
-    handlebar_data.insert("name", name);
-    handlebar_data.insert("version", version);
-    handlebar_data.insert("sources", sources_str);
-    handlebar_data.insert("output", output_str);
+/// Generates the Doxyfile and prints it, with nothing else on stdout.
+fn print_doxyfile(
+    mut options: GenerateOptions,
+    args: &Arguments,
+    matches: &ArgMatches,
+) -> Result<()> {
+    if !args.more_srcs.is_empty() {
+        return Err(anyhow!("--print-doxyfile only works for a single package"));
+    }
+    options.apply_config(matches)?;
+    // Progress and notices would end up in the piped Doxyfile
+    options.quiet = true;
+    options.doxyfile_only = true;
+    let report = generate(options)?;
+    print!("{}", fs::read_to_string(&report.doxyfile)?);
+    Ok(())
+}
 
-    let doxy_folder_out = format!("{}/.doxy", output_str);
-    let doxy_file_out = format!("{}/DoxyFile", &doxy_folder_out);
+/// Prints the effective settings for each package, as text or, with
+/// `--json`, as a list with one entry per package.
+fn print_config(options: GenerateOptions, args: &Arguments, matches: &ArgMatches) -> Result<()> {
+    let srcs = [vec![options.src.clone()], args.more_srcs.clone()].concat();
+    let mut configs = Vec::new();
+    for src in srcs {
+        let mut options = GenerateOptions {
+            src,
+            quiet: true,
+            ..options.clone()
+        };
+        options.apply_config(matches)?;
+        configs.push(options.effective_config(matches)?);
+    }
+    if args.json {
+        println!("{}", json!(configs));
+        return Ok(());
+    }
+    for (i, config) in configs.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        for setting in config.as_array().into_iter().flatten() {
+            let value = match &setting["value"] {
+                Value::String(value) => value.clone(),
+                Value::Array(values) if values.is_empty() => String::from("none"),
+                Value::Array(values) => values
+                    .iter()
+                    .map(|value| value.as_str().map_or(value.to_string(), String::from))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Value::Null => String::from("none"),
+                value => value.to_string(),
+            };
+            println!(
+                "{:<14} {} {}",
+                setting["name"].as_str().unwrap_or_default(),
+                value.green(),
+                format!("({})", setting["from"].as_str().unwrap_or_default()).dimmed()
+            );
+        }
+    }
+    Ok(())
+}
 
-    fs::create_dir_all(&doxy_folder_out).expect("Unable to create directory");
-    let mut output_file = File::create(&doxy_file_out)?;
+/// The folder a package's docs go into below a shared `--out`: the package
+/// folder's name, or the name in a reference such as `fmt/9.1.0@`.
+fn output_folder_name(src: &Path) -> PathBuf {
+    match src.canonicalize() {
+        Ok(path) => path.file_name().map(PathBuf::from).unwrap_or_default(),
+        Err(_) => src
+            .components()
+            .next()
+            .map(|name| PathBuf::from(name.as_os_str()))
+            .unwrap_or_default(),
+    }
+}
 
-    handlebars.register_template_file("doxyfile", "./template/DoxyFile.hbs")?;
+/// Documents one of several packages, with its own config file and, below a
+/// shared `--out`, its own output folder.
+fn document_package(
+    options: &GenerateOptions,
+    src: &Path,
+    args: &Arguments,
+    matches: &ArgMatches,
+    parallel: bool,
+) -> Result<GenerateReport> {
+    let mut options = options.clone();
+    options.src = src.to_path_buf();
+    options.out = options
+        .out
+        .as_ref()
+        .map(|out| out.join(output_folder_name(src)));
+    // Progress from packages documented side by side would be interleaved
+    options.quiet |= args.json || parallel;
+    options.apply_config(matches)?;
+    let quiet = options.quiet;
+    let report = generate(options)?;
+    if !args.json && !parallel {
+        report_docs(&report, false, None, None, quiet)?;
+    }
+    if args.fail_on_warnings && report.warnings > 0 {
+        return Err(FailedOnWarnings(report).into());
+    }
+    Ok(report)
+}
+
+type PackageResult = (PathBuf, Result<GenerateReport>);
+
+/// Documents `srcs` on `jobs` threads, with a progress line per package.
+fn document_parallel(
+    options: &GenerateOptions,
+    srcs: Vec<PathBuf>,
+    jobs: usize,
+    args: &Arguments,
+    matches: &ArgMatches,
+) -> Vec<PackageResult> {
+    let show = !args.json && !options.quiet && options.progress != Some(Progress::None);
+    let bars = MultiProgress::new();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                while let Some(src) = srcs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let pb = if show {
+                        bars.add(ProgressBar::new_spinner())
+                    } else {
+                        ProgressBar::hidden()
+                    };
+                    pb.set_style(
+                        ProgressStyle::default_spinner()
+                            .template("{spinner} {prefix:.bold} {wide_msg} [{elapsed_precise}]")
+                            .expect("valid template"),
+                    );
+                    pb.set_prefix(src.display().to_string());
+                    pb.set_message(format!("{}", "Generating...".yellow()));
+                    pb.enable_steady_tick(Duration::from_millis(50));
+                    let result = document_package(options, src, args, matches, true);
+                    let outcome = match &result {
+                        Ok(report) => format!("Doxygen produced {} warnings", report.warnings)
+                            .green()
+                            .to_string(),
+                        Err(err) => format!("Error: {:#}", err).red().to_string(),
+                    };
+                    // Without a terminal to draw on, say how it went in a line
+                    if show && pb.is_hidden() {
+                        println!("{}: {}", src.display(), outcome);
+                    }
+                    pb.finish_with_message(outcome);
+                    results
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push((src.clone(), result));
+                }
+            });
+        }
+    });
 
-    handlebars.render_to_write("doxyfile", &handlebar_data, &mut output_file)?;
-    Ok((
-        String::from("Generated DoxyFile"),
-        PathBuf::from(doxy_file_out),
-    ))
+    // Report in the order the packages were given, not the order they finished
+    let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    results.sort_by_key(|(src, _)| srcs.iter().position(|s| s == src));
+    results
 }
 
-fn main() -> Result<()> {
-    let args = Arguments::parse();
+/// Documents each of `srcs`, `--jobs` at a time, carrying on past packages
+/// that fail and summing up how each one went at the end.
+fn document_all(
+    options: GenerateOptions,
+    srcs: Vec<PathBuf>,
+    args: &Arguments,
+    matches: &ArgMatches,
+) -> Result<()> {
+    if args.serve.is_some() || args.watch {
+        return Err(anyhow!(
+            "--serve and --watch only work when documenting a single package"
+        ));
+    }
 
-    if let Some(src_pkg) = args.src.to_str() {
-        // conan inspect
-        let (name, version, requires) = inspect(src_pkg)?;
-        println!(
-            "Generating documentation for {}/{} with \n {:#?}",
-            name.green(),
-            version.green(),
-            requires
-        );
+    let jobs = usize::from(args.jobs).min(srcs.len());
+    let results = if jobs > 1 {
+        document_parallel(&options, srcs, jobs, args, matches)
+    } else {
+        srcs.into_iter()
+            .map(|src| {
+                if !args.json && !options.quiet {
+                    println!("\n{}", format!("==> {}", src.display()).bold());
+                }
+                let result = document_package(&options, &src, args, matches, false);
+                if let (false, Err(err)) = (args.json, &result) {
+                    eprintln!("Error: {}", format!("{:#}", err).red());
+                }
+                (src, result)
+            })
+            .collect()
+    };
 
-        // conan install
-        with_progress_bar("[1/5] Fetching packages...".to_string(), || {
-            conan_install(src_pkg)
-        })?;
-
-        // conan info
-        let source_folders = with_progress_bar("[2/5] Gathering Sources...".to_string(), || {
-            gather_sources(src_pkg)
-        })?;
-
-        // output path
-        let output_str = with_progress_bar("[3/5] Resolving Output...".to_string(), || {
-            let output_default =
-                PathBuf::from(format!("{}/build/docs/{}_{}", src_pkg, name, version));
-            let output_str = args
-                .out
-                .unwrap_or(output_default)
-                .to_str()
-                .ok_or_else(|| anyhow!("Failed to convert PathBuf to str"))?
-                .to_string();
-            Ok((format!("Output location is {}", output_str), output_str))
-        })?;
-
-        // Generate DoxyFile
-        let doxy_file_out = with_progress_bar("[4/5] Generating Doxyfile...".to_string(), || {
-            generate_doxyfile(&name, &version, &source_folders.join(" "), &output_str)
-        })?;
-
-        // Doxygen generate
-        let status = with_progress_bar("[5/5] Running Doxygen...".to_string(), || {
-            let status = Command::new("doxygen")
-                .args([
-                    &doxy_file_out
-                        .to_str()
-                        .ok_or(anyhow!("outpath could not be resolved"))?,
-                    "-l",
-                    "./template/Layout.xml"
-                ])
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status()
-                .expect("Failed to execute command");
-
-            Ok((String::from("Finished Doxygen Generate"), status))
-        })?;
-
-        // open if success
-        if status.success() {
-            let path_to_html =
-                PathBuf::from(format!("{}/html/index.html", &output_str)).canonicalize()?;
-            let html_os_str = path_to_html.as_os_str().to_owned();
-            let html = html_os_str.to_str().ok_or(anyhow!(" "))?;
-            println!("\n Success: Docs can be found at {}", html.green());
-
-            if args.open {
-                match open(html) {
-                    Ok(()) => println!("Opened '{}' successfully.", html),
-                    Err(err) => eprintln!("An error occurred when opening '{}': {}", html, err),
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    let reports: Vec<&GenerateReport> = results
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok())
+        .collect();
+    let index_root = options
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("build/docs"));
+    let index = write_index(&index_root, &reports)?;
+    if !args.json && !options.quiet {
+        println!("\n Summary:");
+    }
+    if args.json {
+        let summaries: Vec<_> = results
+            .iter()
+            .map(|(src, result)| match result {
+                Ok(report) => {
+                    let mut summary = report.summary();
+                    summary["src"] = json!(src);
+                    summary
                 }
+                Err(err) => {
+                    let mut summary = error_json(err);
+                    summary["src"] = json!(src);
+                    summary
+                }
+            })
+            .collect();
+        println!("{}", json!({ "index": index, "packages": summaries }));
+        if failed > 0 {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    for (src, result) in results.iter().filter(|_| !options.quiet) {
+        match result {
+            Ok(report) => println!(
+                "  {} {} ({} warnings)",
+                format!("{}/{}", report.package.name, report.package.version).green(),
+                report.docs.first().unwrap_or(&report.doxyfile).display(),
+                report.warnings
+            ),
+            Err(err) => println!("  {} failed: {:#}", src.display().to_string().red(), err),
+        }
+    }
+
+    if let Some(index) = &index {
+        let index = index.display().to_string();
+        println!(
+            "\n Success: Index of all packages can be found at {}",
+            index.green()
+        );
+        if args.wants_open() {
+            if let Err(err) = open_in(&index, args.open_with.as_deref()) {
+                eprintln!("An error occurred when opening '{}': {}", index, err);
             }
-        } else {
-            return Err(anyhow!(
-                "Failed to generate docs. Please ensure doxygen is available in PATH."
-            ));
         }
     }
 
+    if failed > 0 {
+        return Err(anyhow!("{} of {} packages failed", failed, results.len()));
+    }
     Ok(())
 }
+
+fn main() -> Result<()> {
+    // Escape codes only belong on a terminal, see https://no-color.org
+    if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) || !io::stdout().is_terminal() {
+        colored::control::set_override(false);
+    }
+
+    handle_interrupts()?;
+
+    let matches = Arguments::command().get_matches();
+    let mut args = Arguments::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let result = match (args.command.take(), args.options.take()) {
+        (
+            Some(Commands::Doctor {
+                conan_path,
+                doxygen_path,
+            }),
+            _,
+        ) => doctor(&SystemRunner, &conan_path, &doxygen_path),
+        (Some(Commands::Completions { shell }), _) => {
+            completions(shell, &mut Arguments::command(), &mut io::stdout()).map_err(Into::into)
+        }
+        (None, Some(options)) if args.print_config => print_config(options, &args, &matches),
+        (None, Some(options)) if args.print_doxyfile => print_doxyfile(options, &args, &matches),
+        (None, Some(options)) if args.more_srcs.is_empty() => document(options, &args, &matches),
+        (None, Some(options)) => {
+            let srcs = [vec![options.src.clone()], args.more_srcs.clone()].concat();
+            document_all(options, srcs, &args, &matches)
+        }
+        (None, None) => Err(anyhow!("Missing path to conan package")),
+    };
+
+    if let (true, Err(err)) = (args.json, &result) {
+        println!("{}", error_json(err));
+        process::exit(1);
+    }
+    result
+}
@@ -0,0 +1,381 @@
+use std::{
+    env,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+
+use crate::{
+    error::Error,
+    interrupt::{self, Running},
+};
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Resolves a program the same way the shell would: paths are checked
+/// directly, bare names are searched for in PATH.
+pub(crate) fn which(program: &Path) -> Option<PathBuf> {
+    let candidates = |p: PathBuf| {
+        let with_ext = p.with_extension(env::consts::EXE_EXTENSION);
+        [p, with_ext]
+    };
+
+    if program.components().count() > 1 {
+        return candidates(program.to_path_buf())
+            .into_iter()
+            .find(|p| is_executable(p));
+    }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .flat_map(|dir| candidates(dir.join(program)))
+        .find(|p| is_executable(p))
+}
+
+/// The seam between the pipeline and the conan/doxygen subprocesses, so they
+/// can be replaced with canned output.
+pub trait CommandRunner {
+    /// Runs a program to completion, capturing its output.
+    fn run(&self, program: &str, args: &[&str]) -> Result<Output>;
+
+    /// Runs a program with its output going straight to the terminal.
+    fn stream(&self, program: &str, args: &[&str]) -> Result<ExitStatus> {
+        Ok(self.run(program, args)?.status)
+    }
+
+    /// Like [`run`](Self::run), but stops the program with an error once
+    /// `timeout` has passed. Runners that can't stop programs ignore it.
+    fn run_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        _timeout: Option<Duration>,
+    ) -> Result<Output> {
+        self.run(program, args)
+    }
+
+    /// Like [`stream`](Self::stream), but stops the program with an error
+    /// once `timeout` has passed.
+    fn stream_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        _timeout: Option<Duration>,
+    ) -> Result<ExitStatus> {
+        self.stream(program, args)
+    }
+
+    /// Like [`run_timeout`](Self::run_timeout), with `env` set on top of the
+    /// inherited environment. Runners that don't start processes ignore it.
+    fn run_env(
+        &self,
+        program: &str,
+        args: &[&str],
+        _env: &[(String, String)],
+        timeout: Option<Duration>,
+    ) -> Result<Output> {
+        self.run_timeout(program, args, timeout)
+    }
+
+    /// Like [`stream_timeout`](Self::stream_timeout), with `env` set on top of
+    /// the inherited environment.
+    fn stream_env(
+        &self,
+        program: &str,
+        args: &[&str],
+        _env: &[(String, String)],
+        timeout: Option<Duration>,
+    ) -> Result<ExitStatus> {
+        self.stream_timeout(program, args, timeout)
+    }
+
+    /// Where the program would be run from, `None` when it can't be found.
+    fn resolve(&self, program: &Path) -> Option<PathBuf> {
+        which(program)
+    }
+}
+
+/// Starts a program, registered to be stopped if the run is interrupted.
+/// With interrupts handled it gets a process group of its own, so stopping
+/// it also stops whatever it started.
+fn spawn(command: &mut Command, program: &str) -> Result<(Child, Running)> {
+    #[cfg(unix)]
+    if interrupt::installed() {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let child = command
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run '{}': {}", program, e))?;
+    let running = Running::new(&child);
+    Ok((child, running))
+}
+
+/// Waits for `child`, killing it once `timeout` has passed.
+fn wait(child: &mut Child, program: &str, timeout: Option<Duration>) -> Result<ExitStatus> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+    let Some(timeout) = timeout else {
+        return Ok(child.wait()?);
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            interrupt::kill(child.id());
+            let _ = child.wait();
+            return Err(Error::TimedOut {
+                program: program.to_string(),
+                seconds: timeout.as_secs(),
+            }
+            .into());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Reads a child's pipe on its own thread, so a chatty program can't block
+/// on a full pipe while it is waited for.
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Runs commands as real subprocesses.
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<Output> {
+        self.run_timeout(program, args, None)
+    }
+
+    fn stream(&self, program: &str, args: &[&str]) -> Result<ExitStatus> {
+        self.stream_timeout(program, args, None)
+    }
+
+    fn run_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<Output> {
+        self.run_env(program, args, &[], timeout)
+    }
+
+    fn stream_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<ExitStatus> {
+        self.stream_env(program, args, &[], timeout)
+    }
+
+    fn run_env(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(String, String)],
+        timeout: Option<Duration>,
+    ) -> Result<Output> {
+        let (mut child, _running) = spawn(
+            Command::new(program)
+                .args(args)
+                .envs(env.iter().cloned())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+            program,
+        )?;
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+        // On a timeout the readers are left behind, a grandchild may still
+        // hold the pipes open
+        let status = wait(&mut child, program, timeout)?;
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+
+    fn stream_env(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(String, String)],
+        timeout: Option<Duration>,
+    ) -> Result<ExitStatus> {
+        let (mut child, _running) = spawn(
+            Command::new(program).args(args).envs(env.iter().cloned()),
+            program,
+        )?;
+        wait(&mut child, program, timeout)
+    }
+}
+
+/// Sets `env` on every program `runner` starts, on top of what the caller
+/// asks for.
+pub(crate) struct WithEnv<'a> {
+    pub(crate) runner: &'a dyn CommandRunner,
+    pub(crate) env: Vec<(String, String)>,
+}
+
+impl CommandRunner for WithEnv<'_> {
+    fn run(&self, program: &str, args: &[&str]) -> Result<Output> {
+        self.run_env(program, args, &[], None)
+    }
+
+    fn stream(&self, program: &str, args: &[&str]) -> Result<ExitStatus> {
+        self.stream_env(program, args, &[], None)
+    }
+
+    fn run_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<Output> {
+        self.run_env(program, args, &[], timeout)
+    }
+
+    fn stream_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<ExitStatus> {
+        self.stream_env(program, args, &[], timeout)
+    }
+
+    fn run_env(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(String, String)],
+        timeout: Option<Duration>,
+    ) -> Result<Output> {
+        let env = [self.env.as_slice(), env].concat();
+        self.runner.run_env(program, args, &env, timeout)
+    }
+
+    fn stream_env(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(String, String)],
+        timeout: Option<Duration>,
+    ) -> Result<ExitStatus> {
+        let env = [self.env.as_slice(), env].concat();
+        self.runner.stream_env(program, args, &env, timeout)
+    }
+
+    fn resolve(&self, program: &Path) -> Option<PathBuf> {
+        self.runner.resolve(program)
+    }
+}
+
+pub(crate) fn as_strs(args: &[String]) -> Vec<&str> {
+    args.iter().map(String::as_str).collect()
+}
+
+/// Formats a command line the way it would be typed into a shell.
+pub(crate) fn command_line(program: &str, args: &[&str]) -> String {
+    std::iter::once(&program)
+        .chain(args)
+        .map(|arg| {
+            if arg.contains(' ') {
+                format!("\"{}\"", arg)
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub(crate) fn print_command(program: &str, args: &[&str]) {
+    println!("{}", command_line(program, args).cyan());
+}
+
+/// The last few lines of a subprocess' stderr, which is where conan reports
+/// the actual failure.
+pub(crate) fn stderr_tail(stderr: &[u8]) -> String {
+    const TAIL_LINES: usize = 20;
+    let stderr = String::from_utf8_lossy(stderr);
+    let lines = stderr.trim_end().lines().collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(TAIL_LINES)..].join("\n")
+}
+
+/// Runs a command to completion within `timeout`, turning a non-zero exit
+/// into an error that carries the tail of its stderr.
+pub(crate) fn checked_output(
+    runner: &dyn CommandRunner,
+    program: &str,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> Result<Output> {
+    let output = runner.run_timeout(program, args, timeout)?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`{}` failed ({}):\n{}",
+            command_line(program, args),
+            output.status,
+            stderr_tail(&output.stderr)
+        ));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_lines_of_stderr() {
+        let stderr: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+
+        let tail = stderr_tail(format!("{}\n\n", stderr).as_bytes());
+
+        let lines: Vec<&str> = tail.lines().collect();
+        assert_eq!(lines.len(), 20);
+        assert_eq!(lines.first(), Some(&"line 11"));
+        assert_eq!(lines.last(), Some(&"line 30"));
+        assert_eq!(stderr_tail(b"ERROR: missing\n"), "ERROR: missing");
+        assert_eq!(stderr_tail(b""), "");
+    }
+
+    #[test]
+    fn quotes_arguments_with_spaces_in_printed_commands() {
+        assert_eq!(
+            command_line(
+                "conan",
+                &["install", "my pkg", "-pr", "default", "--build=missing"]
+            ),
+            "conan install \"my pkg\" -pr default --build=missing"
+        );
+        assert_eq!(command_line("doxygen", &[]), "doxygen");
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::interrupt::{register_scratch, unregister_scratch};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A working directory under the system temp dir for files only needed while
+/// generating, e.g. the conan install folder and the Doxyfile. It is removed
+/// when dropped unless it was asked to be kept.
+#[derive(Debug)]
+pub(crate) struct ScratchDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl ScratchDir {
+    pub(crate) fn new(keep: bool) -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let path = env::temp_dir().join(format!(
+            "conan-doxygen-{}-{}-{}",
+            process::id(),
+            nanos,
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).map_err(|e| {
+            anyhow!(
+                "Failed to create scratch directory '{}': {}",
+                path.display(),
+                e
+            )
+        })?;
+        if !keep {
+            register_scratch(&path);
+        }
+        Ok(ScratchDir { path, keep })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if !self.keep {
+            unregister_scratch(&self.path);
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Decodes the `%20`-style escapes browsers use in request paths.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Maps a request path onto a file under `root`, refusing anything that
+/// would step outside of it.
+fn resolve(root: &Path, target: &str) -> Option<PathBuf> {
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(path);
+    let relative = Path::new(path.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let file = root.join(relative);
+    if file.is_dir() {
+        Some(file.join("index.html"))
+    } else {
+        Some(file)
+    }
+}
+
+/// Sends a response. Answers to HEAD requests leave the body out, but keep
+/// its length.
+fn respond(stream: &mut TcpStream, head: bool, status: &str, content_type: &str, body: &[u8]) {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    let body = if head { &[][..] } else { body };
+    let _ = stream
+        .write_all(header.as_bytes())
+        .and_then(|_| stream.write_all(body));
+}
+
+fn handle(root: &Path, mut stream: TcpStream) {
+    let mut request_line = String::new();
+    if BufReader::new(&stream)
+        .read_line(&mut request_line)
+        .is_err()
+    {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(
+            &mut stream,
+            false,
+            "400 Bad Request",
+            "text/plain",
+            b"Bad Request",
+        );
+    };
+    let head = method == "HEAD";
+    if method != "GET" && !head {
+        return respond(
+            &mut stream,
+            false,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Method Not Allowed",
+        );
+    }
+
+    match resolve(root, target).and_then(|file| fs::read(&file).ok().map(|body| (file, body))) {
+        Some((file, body)) => respond(&mut stream, head, "200 OK", content_type(&file), &body),
+        None => respond(
+            &mut stream,
+            head,
+            "404 Not Found",
+            "text/plain",
+            b"Not Found",
+        ),
+    }
+}
+
+/// Serves the files under `root` on localhost until the process is stopped.
+/// Doxygen's search box needs its pages served over http rather than opened
+/// from disk. `on_ready` gets the url once the port is bound.
+pub fn serve(root: &Path, port: u16, on_ready: impl FnOnce(&str)) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| anyhow!("Failed to listen on port {}: {}", port, e))?;
+    let url = format!("http://localhost:{}/", listener.local_addr()?.port());
+    on_ready(&url);
+
+    for stream in listener.incoming().flatten() {
+        let root = root.to_path_buf();
+        thread::spawn(move || handle(&root, stream));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    /// Sends `request` to a server for `root`, returning the whole response.
+    fn request(root: &Path, request: &str) -> String {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        handle(root, server);
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn answers_head_with_the_length_of_the_body_it_leaves_out() {
+        let scratch = ScratchDir::new(false).unwrap();
+        fs::write(scratch.path().join("index.html"), "<p>docs</p>").unwrap();
+
+        let get = request(scratch.path(), "GET / HTTP/1.1\r\n\r\n");
+        assert!(get.starts_with("HTTP/1.1 200 OK\r\n"), "{}", get);
+        assert!(get.contains("Content-Length: 11\r\n"), "{}", get);
+        assert!(get.ends_with("\r\n\r\n<p>docs</p>"), "{}", get);
+
+        let head = request(scratch.path(), "HEAD /index.html HTTP/1.1\r\n\r\n");
+        assert!(head.contains("Content-Length: 11\r\n"), "{}", head);
+        assert!(head.ends_with("\r\n\r\n"), "{}", head);
+
+        let missing = request(scratch.path(), "HEAD /missing.html HTTP/1.1\r\n\r\n");
+        assert!(
+            missing.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            missing
+        );
+        assert!(missing.ends_with("\r\n\r\n"), "{}", missing);
+    }
+}
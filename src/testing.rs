@@ -0,0 +1,72 @@
+//! Helpers shared by the unit tests.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+
+use crate::process::{command_line, CommandRunner};
+
+pub(crate) fn exit_status(code: i32) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(code << 8)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(code as u32)
+    }
+}
+
+/// Answers commands with canned output instead of running them, and records
+/// each command line it was asked to run. Programs are found unless listed
+/// as missing.
+pub(crate) struct FakeRunner<F> {
+    respond: F,
+    missing: Vec<&'static str>,
+    calls: Mutex<Vec<String>>,
+}
+
+impl<F: Fn(&str, &[&str]) -> (i32, String)> FakeRunner<F> {
+    /// `respond` gets the program and its arguments, and returns the exit
+    /// code and stdout.
+    pub(crate) fn new(respond: F) -> Self {
+        FakeRunner {
+            respond,
+            missing: Vec::new(),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn without(mut self, program: &'static str) -> Self {
+        self.missing.push(program);
+        self
+    }
+
+    /// The command lines run so far, in order.
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl<F: Fn(&str, &[&str]) -> (i32, String)> CommandRunner for FakeRunner<F> {
+    fn run(&self, program: &str, args: &[&str]) -> Result<Output> {
+        self.calls.lock().unwrap().push(command_line(program, args));
+        let (code, stdout) = (self.respond)(program, args);
+        Ok(Output {
+            status: exit_status(code),
+            stdout: stdout.into_bytes(),
+            stderr: Vec::new(),
+        })
+    }
+
+    fn resolve(&self, program: &Path) -> Option<PathBuf> {
+        let missing = self.missing.iter().any(|name| Path::new(name) == program);
+        (!missing).then(|| program.to_path_buf())
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+
+use crate::{doxygen::run_doxygen, CommandRunner, GenerateOptions, GenerateReport};
+
+/// What is watched when `--file-patterns` isn't given.
+const SOURCE_EXTENSIONS: [&str; 9] = ["h", "hh", "hpp", "hxx", "c", "cc", "cpp", "cxx", "md"];
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether `name` matches a doxygen file pattern, where `*` stands for any
+/// run of characters and `?` for any one.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    // Where the last `*` was and the name position it is tried to match up to
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether doxygen reads `path`: it matches one of `patterns`, or without
+/// any has one of the usual source extensions.
+fn is_source(path: &Path, patterns: &[String]) -> bool {
+    if patterns.is_empty() {
+        return path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext));
+    }
+    let name = path.file_name().and_then(|name| name.to_str());
+    name.is_some_and(|name| {
+        patterns
+            .iter()
+            .any(|pattern| matches_pattern(pattern, name))
+    })
+}
+
+/// Modification times of the source files below `folders`, the ones matching
+/// `patterns`. Scratch folders and the output are skipped so a rebuild
+/// doesn't trigger another one.
+fn snapshot(
+    folders: &[PathBuf],
+    output: &Path,
+    patterns: &[String],
+) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    let mut pending = folders.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                let scratch = matches!(
+                    path.file_name().and_then(|name| name.to_str()),
+                    Some(".conan" | ".doxy" | ".git")
+                );
+                if !scratch && !path.starts_with(output) {
+                    pending.push(path);
+                }
+            } else if is_source(&path, patterns) {
+                if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                    files.insert(path, modified);
+                }
+            }
+        }
+    }
+    files
+}
+
+/// Blocks until the sources change, then until they have stopped changing,
+/// so saving several files at once triggers a single rebuild.
+fn wait_for_change(folders: &[PathBuf], output: &Path, patterns: &[String]) {
+    let mut last = snapshot(folders, output, patterns);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = snapshot(folders, output, patterns);
+        if current != last {
+            last = current;
+            break;
+        }
+    }
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = snapshot(folders, output, patterns);
+        if current == last {
+            return;
+        }
+        last = current;
+    }
+}
+
+/// Re-runs doxygen whenever the sources of a finished run change. conan isn't
+/// run again, so changes to the package's dependencies need a fresh run.
+pub fn watch(
+    report: &GenerateReport,
+    options: &GenerateOptions,
+    runner: &dyn CommandRunner,
+) -> Result<()> {
+    let doxygen = options
+        .doxygen_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("doxygen"));
+    let doxygen = doxygen
+        .to_str()
+        .ok_or_else(|| anyhow!("Failed to convert doxygen path to str"))?;
+    let output = report.output.canonicalize()?;
+
+    loop {
+        println!(
+            "\n Watching {} source folders for changes (press Ctrl-C to stop)",
+            report.sources.len()
+        );
+        wait_for_change(&report.sources, &output, &options.file_patterns);
+        println!("{}", "Sources changed, running doxygen...".yellow());
+        let run = run_doxygen(
+            runner,
+            doxygen,
+            &report.doxyfile,
+            &report.warnings_log,
+            options.timeout(),
+            options.verbose,
+            false,
+        );
+        // A failed rebuild shouldn't end watch mode, the next change may fix it
+        let (msg, result) = match run {
+            Ok(run) => run,
+            Err(e) => {
+                println!("Error: {}", format!("{:#}", e).red());
+                continue;
+            }
+        };
+        match result {
+            Some((status, _)) if !status.success() => {
+                println!("Error: {}", format!("doxygen exited with {}", status).red())
+            }
+            _ => println!("{}", msg.green()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    #[test]
+    fn matches_doxygen_file_patterns() {
+        assert!(matches_pattern("*.hxx", "vector.hxx"));
+        assert!(matches_pattern("*.h*", "vector.hpp"));
+        assert!(matches_pattern("v?ctor.*", "vector.h"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("*.h", "vector.hxx"));
+        assert!(!matches_pattern("*.hxx", "hxx"));
+        assert!(!matches_pattern("v?ctor", "vctor"));
+    }
+
+    #[test]
+    fn watches_the_files_doxygen_reads() {
+        let scratch = ScratchDir::new(false).unwrap();
+        let sources = scratch.path().join("sources");
+        fs::create_dir_all(sources.join("include")).unwrap();
+        for name in ["lib.ixx", "lib.h", "notes.txt"] {
+            fs::write(sources.join("include").join(name), "").unwrap();
+        }
+        let output = scratch.path().join("out");
+        let watched = |patterns: &[String]| {
+            let mut names: Vec<String> =
+                snapshot(std::slice::from_ref(&sources), &output, patterns)
+                    .keys()
+                    .filter_map(|path| path.file_name()?.to_str().map(String::from))
+                    .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(watched(&[]), ["lib.h"]);
+        assert_eq!(
+            watched(&[String::from("*.ixx"), String::from("*.txt")]),
+            ["lib.ixx", "notes.txt"]
+        );
+    }
+}
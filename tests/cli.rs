@@ -0,0 +1,109 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+const BIN: &str = env!("CARGO_BIN_EXE_conan-doxygen");
+
+/// A folder below the system temp dir, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("conan-doxygen-{}-{}", name, process::id()));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The quoted value of `key` in a Doxyfile.
+fn quoted_value<'a>(doxyfile: &'a str, key: &str) -> &'a str {
+    doxyfile
+        .lines()
+        .find_map(|line| line.strip_prefix(key))
+        .and_then(|rest| rest.trim_start().strip_prefix('='))
+        .map(|value| value.trim().trim_end_matches('\\').trim().trim_matches('"'))
+        .unwrap_or_default()
+}
+
+/// The embedded template and layout have to work wherever the binary is run
+/// from, not just the repository. The binary itself stands in for conan and
+/// doxygen, which a conanfile.txt without dependencies doesn't need.
+#[test]
+fn renders_the_doxyfile_from_another_directory() {
+    let root = TempDir::new("cli");
+    let pkg = root.0.join("pkg");
+    let cwd = root.0.join("elsewhere");
+    fs::create_dir_all(pkg.join("sources")).unwrap();
+    fs::create_dir_all(&cwd).unwrap();
+    fs::write(pkg.join("conanfile.txt"), "[requires]\n").unwrap();
+    fs::write(pkg.join("sources").join("pkg.h"), "").unwrap();
+
+    let output = Command::new(BIN)
+        .current_dir(&cwd)
+        .arg(&pkg)
+        .args(["--no-install", "--no-deps", "--print-doxyfile"])
+        .args(["--conan-path", BIN, "--doxygen-path", BIN])
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    let doxyfile = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(quoted_value(&doxyfile, "PROJECT_NAME"), "pkg");
+    let layout = Path::new(quoted_value(&doxyfile, "LAYOUT_FILE"));
+    assert!(fs::read_to_string(layout)
+        .unwrap()
+        .contains("<doxygenlayout"));
+    let stylesheet = Path::new(quoted_value(&doxyfile, "HTML_EXTRA_STYLESHEET"));
+    assert!(stylesheet.is_file());
+    // --print-doxyfile keeps its scratch directory for doxygen to read
+    if let Some(scratch) = layout.parent().and_then(Path::parent) {
+        let _ = fs::remove_dir_all(scratch);
+    }
+}
+
+/// A printed Doxyfile is run from wherever it is saved, so relative folders
+/// on the command line end up absolute in it.
+#[test]
+fn prints_absolute_folders_for_relative_arguments() {
+    let root = TempDir::new("relative");
+    let pkg = root.0.join("pkg");
+    fs::create_dir_all(pkg.join("sources")).unwrap();
+    fs::create_dir_all(root.0.join("images")).unwrap();
+    fs::write(pkg.join("conanfile.txt"), "[requires]\n").unwrap();
+    fs::write(pkg.join("sources").join("pkg.h"), "").unwrap();
+
+    let output = Command::new(BIN)
+        .current_dir(&root.0)
+        .arg("pkg")
+        .args(["--no-install", "--no-deps", "--print-doxyfile"])
+        .args(["--image-path", "images"])
+        .args(["--conan-path", BIN, "--doxygen-path", BIN])
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    let doxyfile = String::from_utf8(output.stdout).unwrap();
+    let root = root.0.canonicalize().unwrap();
+    assert_eq!(
+        Path::new(quoted_value(&doxyfile, "INPUT")),
+        root.join("pkg").join("sources")
+    );
+    assert_eq!(
+        Path::new(quoted_value(&doxyfile, "IMAGE_PATH")),
+        root.join("images")
+    );
+    let layout = Path::new(quoted_value(&doxyfile, "LAYOUT_FILE"));
+    if let Some(scratch) = layout.parent().and_then(Path::parent) {
+        let _ = fs::remove_dir_all(scratch);
+    }
+}